@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 use std::process::exit;
 use std::env::current_dir;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use deferred_box::DeferredBox;
 use teo_result::{Error, Result};
 use teo_runtime::namespace::Namespace;
@@ -25,6 +26,7 @@ use crate::app::program::Program;
 use crate::cli::cli_parse::cli_parse;
 use crate::cli::command::CLI;
 use crate::cli::run::run;
+use crate::server::config::ServerConfig;
 
 #[derive(Clone, Debug)]
 pub struct App {
@@ -43,9 +45,13 @@ pub struct Inner {
     #[educe(Debug(ignore))]
     setup: Arc<Mutex<Option<Arc<dyn AsyncCallback>>>>,
     #[educe(Debug(ignore))]
+    shutdown_hooks: Arc<Mutex<Vec<Arc<dyn AsyncCallback>>>>,
+    #[educe(Debug(ignore))]
     programs: Arc<Mutex<BTreeMap<String, Program>>>,
     #[educe(Debug(ignore))]
     conn_ctx: Arc<Mutex<Option<connection::Ctx>>>,
+    server_config: Arc<Mutex<Arc<ServerConfig>>>,
+    ready: AtomicBool,
     app_data: AppData,
 }
 
@@ -86,8 +92,11 @@ impl App {
                 main_namespace: namespace_builder,
                 compiled_main_namespace: DeferredBox::new(),
                 setup: Arc::new(Mutex::new(None)),
+                shutdown_hooks: Arc::new(Mutex::new(vec![])),
                 programs: Arc::new(Mutex::new(btreemap!{})),
                 conn_ctx: Arc::new(Mutex::new(None)),
+                server_config: Arc::new(Mutex::new(Arc::new(ServerConfig::default()))),
+                ready: AtomicBool::new(true),
                 app_data,
             })
         })
@@ -107,6 +116,20 @@ impl App {
         self.inner.setup.lock().unwrap().clone()
     }
 
+    pub fn on_shutdown<A, F>(&self, body: F) where F: AsyncCallbackArgument<A> + 'static {
+        let body = Arc::new(body);
+        self.inner.shutdown_hooks.lock().unwrap().push(Arc::new(move |ctx: transaction::Ctx| {
+            let body = body.clone();
+            async move {
+                body.call(ctx).await
+            }
+        }));
+    }
+
+    pub fn get_shutdown_hooks(&self) -> Vec<Arc<dyn AsyncCallback>> {
+        self.inner.shutdown_hooks.lock().unwrap().clone()
+    }
+
     pub fn program<A, T, F>(&self, name: &str, desc: Option<T>, body: F) where T: Into<String>, F: AsyncCallbackArgument<A> + 'static {
         let body = Arc::new(body);
         self.inner.programs.lock().unwrap().insert(name.to_owned(), Program::new(desc.map(|desc| desc.into()), Arc::new(move |ctx: transaction::Ctx| {
@@ -133,6 +156,32 @@ impl App {
         *self.inner.conn_ctx.lock().unwrap() = Some(ctx);
     }
 
+    pub fn server_config(&self) -> ServerConfig {
+        self.inner.server_config.lock().unwrap().as_ref().clone()
+    }
+
+    /// Takes effect for servers which are already created, from their next request on.
+    /// An invalid config is rejected and the current one is kept.
+    pub fn replace_server_config(&self, config: ServerConfig) -> Result<()> {
+        config.validate()?;
+        *self.inner.server_config.lock().unwrap() = Arc::new(config);
+        Ok(())
+    }
+
+    pub(crate) fn shared_server_config(&self) -> Arc<ServerConfig> {
+        self.inner.server_config.lock().unwrap().clone()
+    }
+
+    /// Whether migration, autoseed and the setup callback have finished. Apps are ready unless
+    /// the serve command is still starting up.
+    pub fn is_ready(&self) -> bool {
+        self.inner.ready.load(Ordering::Acquire)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.inner.ready.store(ready, Ordering::Release);
+    }
+
     pub fn runtime_version(&self) -> RuntimeVersion {
         self.inner.app_data.runtime_version().clone()
     }
@@ -2,7 +2,7 @@ use std::env;
 use clap::{Arg, ArgAction, Command as ClapCommand};
 use teo_runtime::app::entrance::Entrance;
 use teo_runtime::app::runtime_version::RuntimeVersion;
-use super::command::{CLI, CLICommand, GenerateAdminCommand, GenerateClientCommand, GenerateCommand, GenerateEntityCommand, LintCommand, MigrateCommand, PurgeCommand, RunCommand, SeedCommand, SeedCommandAction, ServeCommand};
+use super::command::{CLI, CLICommand, GenerateAdminCommand, GenerateClientCommand, GenerateCommand, GenerateEntityCommand, GenerateOpenApiCommand, LintCommand, MigrateCommand, PurgeCommand, RunCommand, SeedCommand, SeedCommandAction, ServeCommand};
 
 fn make_static_str(s: String) -> &'static str {
     unsafe { &*Box::into_raw(s.into_boxed_str()) }
@@ -105,7 +105,16 @@ pub(crate) fn cli_parse(runtime_version: &RuntimeVersion, entrance: &Entrance, a
                     .num_args(1..)))
             .subcommand(ClapCommand::new("admin")
                 .about("Generate admin dashboard")
-                .arg_required_else_help(false)))
+                .arg_required_else_help(false))
+            .subcommand(ClapCommand::new("openapi")
+                .about("Generate OpenAPI document")
+                .arg_required_else_help(false)
+                .arg(Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .help("The file to write, defaults to openapi.json")
+                    .action(ArgAction::Set)
+                    .num_args(1))))
         .subcommand(ClapCommand::new("migrate")
             .about("Run migration")
             .arg(Arg::new("dry")
@@ -182,6 +191,10 @@ pub(crate) fn cli_parse(runtime_version: &RuntimeVersion, entrance: &Entrance, a
                 Some(("admin", _)) => {
                     CLICommand::Generate(GenerateCommand::GenerateAdminCommand(GenerateAdminCommand {}))
                 }
+                Some(("openapi", submatches)) => {
+                    let output: Option<&String> = submatches.get_one("output");
+                    CLICommand::Generate(GenerateCommand::GenerateOpenApiCommand(GenerateOpenApiCommand { output: output.cloned() }))
+                }
                 _ => unreachable!()
             }
         }
@@ -10,6 +10,7 @@ pub(crate) enum GenerateCommand {
     GenerateClientCommand(GenerateClientCommand),
     GenerateEntityCommand(GenerateEntityCommand),
     GenerateAdminCommand(GenerateAdminCommand),
+    GenerateOpenApiCommand(GenerateOpenApiCommand),
 }
 
 #[derive(Debug)]
@@ -27,6 +28,11 @@ pub(crate) struct GenerateEntityCommand {
 #[derive(Debug)]
 pub(crate) struct GenerateAdminCommand { }
 
+#[derive(Debug)]
+pub(crate) struct GenerateOpenApiCommand {
+    pub(crate) output: Option<String>,
+}
+
 #[derive(Debug)]
 pub(crate) struct MigrateCommand {
     pub(crate) dry: bool,
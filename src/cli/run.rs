@@ -7,6 +7,7 @@ use teo_runtime::schema::load::load_data_sets::load_data_sets;
 use crate::database::connect_databases;
 use crate::server::server::Server;
 use crate::migrate::migrate;
+use crate::openapi;
 use crate::purge::purge;
 use crate::seeder::seed::seed;
 
@@ -16,27 +17,43 @@ pub async fn run(app: &App) -> Result<()> {
         CLICommand::Serve(serve_command) => {
             connect_databases(app, app.compiled_main_namespace(), cli.silent).await?;
             let conn_ctx = app.conn_ctx();
-            // migrate
-            if !serve_command.no_migration {
-                migrate(app, false, false, cli.silent).await?;
-            }
-            // seed auto seed data sets
-            if !serve_command.no_autoseed {
-                if app.compiled_main_namespace().database().is_some() {
-                    let mut diagnostics = Diagnostics::new();
-                    let data_sets = load_data_sets(app.main_namespace(), None, false, app.schema(), &mut diagnostics)?;
+            let server = Server::new(app.clone());
+            app.set_ready(false);
+            // the server accepts connections right away to answer probes, other requests are
+            // refused with 503 until startup finished
+            let startup = async {
+                // migrate
+                if !serve_command.no_migration {
+                    migrate(app, false, false, cli.silent).await?;
+                }
+                // seed auto seed data sets
+                if !serve_command.no_autoseed {
+                    if app.compiled_main_namespace().database().is_some() {
+                        let mut diagnostics = Diagnostics::new();
+                        let data_sets = load_data_sets(app.main_namespace(), None, false, app.schema(), &mut diagnostics)?;
+                        let transaction_ctx = transaction::Ctx::new(app.conn_ctx().clone());
+                        seed(SeedCommandAction::Seed, data_sets, transaction_ctx, false).await?;
+                    }
+                }
+                // setup
+                if let Some(setup) = app.get_setup() {
                     let transaction_ctx = transaction::Ctx::new(app.conn_ctx().clone());
-                    seed(SeedCommandAction::Seed, data_sets, transaction_ctx, false).await?;
+                    setup.call(transaction_ctx).await?;
                 }
-            }
-            // setup
-            if let Some(setup) = app.get_setup() {
-                let transaction_ctx = transaction::Ctx::new(app.conn_ctx().clone());
-                setup.call(transaction_ctx).await?;
-            }
+                app.set_ready(true);
+                Ok(())
+            };
+            let startup = async {
+                let result: Result<()> = startup.await;
+                if result.is_err() {
+                    server.shutdown_handle().shutdown();
+                }
+                result
+            };
             // start server
-            let server = Server::new(app.clone());
-            server.serve(cli.silent).await
+            let (served, started) = tokio::join!(server.serve(cli.silent), startup);
+            started?;
+            served
         }
         CLICommand::Generate(generate_command) => {
             match generate_command {
@@ -88,6 +105,9 @@ pub async fn run(app: &App) -> Result<()> {
                     }
                     Ok(())
                 }
+                GenerateCommand::GenerateOpenApiCommand(command) => {
+                    openapi::generate(app.compiled_main_namespace(), command.output.as_deref()).await
+                }
             }
         }
         CLICommand::Migrate(migrate_command) => {
@@ -1,6 +1,7 @@
 pub mod cli;
 pub mod migrate;
 pub mod purge;
+pub mod openapi;
 pub mod seeder;
 pub mod result;
 pub mod database;
@@ -0,0 +1,420 @@
+use std::path::Path;
+use hyper::Method;
+use serde_json::{json, Map, Value as JsonValue};
+use teo_parser::ast::handler::HandlerInputFormat;
+use teo_parser::r#type::Type;
+use teo_result::Result;
+use teo_runtime::handler::Handler;
+use teo_runtime::model::field::is_optional::IsOptional;
+use teo_runtime::model::field::typed::Typed;
+use teo_runtime::model::Model;
+use teo_runtime::namespace::Namespace;
+use teo_runtime::traits::named::Named;
+use crate::prelude::message::info_message;
+
+/// The builtin model actions dispatched in `Server::process_request` with the arguments they take.
+const BUILTIN_ACTIONS: [(&str, &[&str]); 15] = [
+    ("findUnique", &["where", "select", "include"]),
+    ("findFirst", &["where", "orderBy", "cursor", "take", "skip", "distinct", "select", "include"]),
+    ("findMany", &["where", "orderBy", "cursor", "take", "skip", "pageSize", "pageNumber", "distinct", "select", "include"]),
+    ("create", &["create", "select", "include"]),
+    ("update", &["where", "update", "select", "include"]),
+    ("upsert", &["where", "create", "update", "select", "include"]),
+    ("copy", &["where", "copy", "select", "include"]),
+    ("delete", &["where", "select", "include"]),
+    ("createMany", &["create", "select", "include"]),
+    ("updateMany", &["where", "update", "select", "include"]),
+    ("copyMany", &["where", "copy", "select", "include"]),
+    ("deleteMany", &["where", "select", "include"]),
+    ("count", &["where", "orderBy", "cursor", "take", "skip", "distinct", "select"]),
+    ("aggregate", &["where", "orderBy", "cursor", "take", "skip", "distinct", "_avg", "_sum", "_min", "_max", "_count"]),
+    ("groupBy", &["by", "having", "where", "orderBy", "cursor", "take", "skip", "distinct", "_avg", "_sum", "_min", "_max", "_count"]),
+];
+
+/// Write the OpenAPI document of the app to `output`, `openapi.json` by default.
+pub async fn generate(main_namespace: &Namespace, output: Option<&str>) -> Result<()> {
+    let output = output.unwrap_or("openapi.json");
+    let document = serde_json::to_string_pretty(&openapi_document(main_namespace)).unwrap();
+    if let Some(parent) = Path::new(output).parent() {
+        if !parent.as_os_str().is_empty() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+    }
+    tokio::fs::write(output, document).await?;
+    info_message(format!("OpenAPI document written to \"{}\"", output));
+    Ok(())
+}
+
+/// An OpenAPI 3.1 document describing every route of the handler map.
+pub fn openapi_document(main_namespace: &Namespace) -> JsonValue {
+    let path_prefix = main_namespace.server()
+        .and_then(|server| server.path_prefix.clone())
+        .map(|prefix| prefix.trim_end_matches('/').to_owned())
+        .unwrap_or_default();
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    collect_namespace(main_namespace, main_namespace, &path_prefix, &mut paths, &mut schemas);
+    schemas.insert("Error".to_owned(), error_schema());
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Teo API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "responses": {
+                "Error": {
+                    "description": "The request failed, `code` of the error is the response status",
+                    "content": {
+                        "application/json": {
+                            "schema": schema_ref("Error"),
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn collect_namespace(main_namespace: &Namespace, namespace: &Namespace, path_prefix: &str, paths: &mut Map<String, JsonValue>, schemas: &mut Map<String, JsonValue>) {
+    let namespace_path = namespace.path().iter().map(|name| name.to_string()).collect::<Vec<String>>();
+    for model in namespace.models().values() {
+        let model_name = schema_name(&namespace_path, model.name());
+        schemas.insert(model_name.clone(), model_schema(model));
+        schemas.extend(model_input_schemas(&model_name, model));
+        let mut route = namespace_path.clone();
+        route.push(model.name().to_owned());
+        for (action, args) in BUILTIN_ACTIONS {
+            // the handler map only has routes for the actions the model allows
+            for method in [Method::POST, Method::GET] {
+                let allowed = main_namespace.handler_map().match_all(&method, &route_path("", &route, action))
+                    .is_some_and(|handler_match| handler_match.handler_name() == action);
+                if !allowed {
+                    continue;
+                }
+                let operation = builtin_operation(&model_name, &route, action, args, &method);
+                insert_operation(paths, &route_path(path_prefix, &route, action), &method.as_str().to_lowercase(), operation);
+            }
+        }
+    }
+    for (group_name, group) in namespace.model_handler_groups() {
+        let mut route = namespace_path.clone();
+        route.push(group_name.to_string());
+        for handler in group.handlers().values() {
+            add_handler(paths, path_prefix, &namespace_path, &route, handler);
+        }
+    }
+    for (group_name, group) in namespace.handler_groups() {
+        let mut route = namespace_path.clone();
+        route.push(group_name.to_string());
+        for handler in group.handlers().values() {
+            add_handler(paths, path_prefix, &namespace_path, &route, handler);
+        }
+    }
+    for handler in namespace.handlers().values() {
+        add_handler(paths, path_prefix, &namespace_path, &namespace_path, handler);
+    }
+    for interface in namespace.interfaces().values() {
+        let mut properties = Map::new();
+        let mut required = vec![];
+        for field in interface.fields().values() {
+            properties.insert(field.name().to_owned(), type_schema(field.r#type()));
+            if !field.r#type().is_optional() {
+                required.push(field.name().to_owned());
+            }
+        }
+        schemas.insert(schema_name(&namespace_path, interface.name()), json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        }));
+    }
+    for child in namespace.namespaces().values() {
+        collect_namespace(main_namespace, child, path_prefix, paths, schemas);
+    }
+}
+
+fn add_handler(paths: &mut Map<String, JsonValue>, path_prefix: &str, namespace_path: &[String], route: &[String], handler: &Handler) {
+    let method = format!("{:?}", handler.method()).to_lowercase();
+    let path = match handler.url().map(|url| url.to_string()) {
+        Some(url) => {
+            let prefix = if handler.ignore_prefix() { String::new() } else { route_path(path_prefix, namespace_path, "").trim_end_matches('/').to_owned() };
+            format!("{}/{}", prefix, url.trim_start_matches('/'))
+        }
+        None => route_path(path_prefix, route, handler.name()),
+    };
+    let (path, mut parameters) = path_parameters(&path);
+    let mut operation_id = route.to_vec();
+    operation_id.push(handler.name().to_owned());
+    let mut operation = json!({
+        "operationId": operation_id.join("."),
+        "tags": [tag(route)],
+        "responses": responses(type_schema(handler.output_type())),
+    });
+    if method == "get" || method == "delete" {
+        // the input of these is read from the query string
+        parameters.push(query_parameter(type_schema(handler.input_type())));
+        operation["parameters"] = JsonValue::Array(parameters);
+    } else {
+        let content_types: &[&str] = match handler.format() {
+            HandlerInputFormat::Json => &["application/json"],
+            HandlerInputFormat::Form => &["multipart/form-data", "application/x-www-form-urlencoded"],
+        };
+        if !parameters.is_empty() {
+            operation["parameters"] = JsonValue::Array(parameters);
+        }
+        let content = content_types.iter().map(|content_type| {
+            (content_type.to_string(), json!({ "schema": type_schema(handler.input_type()) }))
+        }).collect::<Map<String, JsonValue>>();
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": content,
+        });
+    }
+    insert_operation(paths, &path, &method, operation);
+}
+
+fn builtin_operation(model_name: &str, route: &[String], action: &str, args: &[&str], method: &Method) -> JsonValue {
+    let input = |suffix: &str| schema_ref(&format!("{}{}", model_name, suffix));
+    let mut properties = Map::new();
+    for arg in args {
+        let schema = match *arg {
+            "take" | "skip" | "pageSize" | "pageNumber" => json!({ "type": "integer" }),
+            "where" | "cursor" => input("WhereInput"),
+            "orderBy" => json!({ "anyOf": [input("OrderByInput"), { "type": "array", "items": input("OrderByInput") }] }),
+            "by" | "distinct" => json!({ "type": "array", "items": input("ScalarFieldEnum") }),
+            "create" if action == "createMany" => json!({ "type": "array", "items": input("CreateInput") }),
+            "create" => input("CreateInput"),
+            "update" | "copy" => input("UpdateInput"),
+            _ => json!({ "type": "object" }),
+        };
+        properties.insert(arg.to_string(), schema);
+    }
+    let data = match action {
+        "findMany" | "createMany" | "updateMany" | "copyMany" | "deleteMany" => json!({
+            "type": "object",
+            "properties": {
+                "data": { "type": "array", "items": schema_ref(model_name) },
+                "meta": { "type": "object", "properties": { "count": { "type": "integer" } } },
+            },
+        }),
+        "count" => json!({ "type": "object", "properties": { "data": { "type": "integer" } } }),
+        "aggregate" => json!({ "type": "object", "properties": { "data": { "type": "object" } } }),
+        "groupBy" => json!({ "type": "object", "properties": { "data": { "type": "array", "items": { "type": "object" } } } }),
+        _ => json!({ "type": "object", "properties": { "data": schema_ref(model_name) } }),
+    };
+    let mut operation_id = route.to_vec();
+    operation_id.push(action.to_owned());
+    let input = json!({ "type": "object", "properties": properties });
+    let mut operation = json!({
+        "tags": [tag(route)],
+        "responses": responses(data),
+    });
+    if *method == Method::GET {
+        // operation IDs are unique per document, the POST route keeps the plain one
+        operation_id.push("get".to_owned());
+        operation["parameters"] = json!([query_parameter(input)]);
+    } else {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": {
+                "application/json": {
+                    "schema": input,
+                },
+            },
+        });
+    }
+    operation["operationId"] = json!(operation_id.join("."));
+    operation
+}
+
+fn model_schema(model: &Model) -> JsonValue {
+    let mut properties = Map::new();
+    let mut required = vec![];
+    for field in model.fields().values() {
+        properties.insert(field.name().to_owned(), type_schema(field.r#type()));
+        if !field.is_optional() {
+            required.push(field.name().to_owned());
+        }
+    }
+    for relation in model.relations().values() {
+        let related = schema_ref(&relation.model_path().join("."));
+        let schema = if relation.is_vec() { json!({ "type": "array", "items": related }) } else { related };
+        properties.insert(relation.name().to_owned(), schema);
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// The inputs of the builtin actions, for the scalar fields of the model. Create inputs list no
+/// required fields since default and generated values are filled in by the runtime.
+fn model_input_schemas(model_name: &str, model: &Model) -> Vec<(String, JsonValue)> {
+    let mut filters = Map::new();
+    let mut values = Map::new();
+    let mut order_by = Map::new();
+    let mut names = vec![];
+    for field in model.fields().values() {
+        let value = type_schema(field.r#type());
+        filters.insert(field.name().to_owned(), json!({
+            "anyOf": [value.clone(), {
+                "type": "object",
+                "properties": {
+                    "equals": value.clone(),
+                    "not": value.clone(),
+                    "in": { "type": "array", "items": value.clone() },
+                    "notIn": { "type": "array", "items": value.clone() },
+                    "lt": value.clone(),
+                    "lte": value.clone(),
+                    "gt": value.clone(),
+                    "gte": value.clone(),
+                },
+            }],
+        }));
+        values.insert(field.name().to_owned(), value);
+        order_by.insert(field.name().to_owned(), json!({ "type": "string", "enum": ["asc", "desc"] }));
+        names.push(field.name().to_owned());
+    }
+    let where_input = format!("{}WhereInput", model_name);
+    filters.insert("AND".to_owned(), json!({ "type": "array", "items": schema_ref(&where_input) }));
+    filters.insert("OR".to_owned(), json!({ "type": "array", "items": schema_ref(&where_input) }));
+    filters.insert("NOT".to_owned(), schema_ref(&where_input));
+    vec![
+        (where_input, json!({ "type": "object", "properties": filters })),
+        (format!("{}CreateInput", model_name), json!({ "type": "object", "properties": values.clone() })),
+        (format!("{}UpdateInput", model_name), json!({ "type": "object", "properties": values })),
+        (format!("{}OrderByInput", model_name), json!({ "type": "object", "properties": order_by })),
+        (format!("{}ScalarFieldEnum", model_name), json!({ "type": "string", "enum": names })),
+    ]
+}
+
+fn type_schema(r#type: &Type) -> JsonValue {
+    match r#type {
+        Type::Null => json!({ "type": "null" }),
+        Type::Bool => json!({ "type": "boolean" }),
+        Type::Int => json!({ "type": "integer", "format": "int32" }),
+        Type::Int64 => json!({ "type": "integer", "format": "int64" }),
+        Type::Float32 => json!({ "type": "number", "format": "float" }),
+        Type::Float => json!({ "type": "number", "format": "double" }),
+        Type::Decimal => json!({ "type": "string", "format": "decimal" }),
+        Type::String => json!({ "type": "string" }),
+        Type::ObjectId => json!({ "type": "string", "pattern": "^[0-9a-f]{24}$" }),
+        Type::Date => json!({ "type": "string", "format": "date" }),
+        Type::DateTime => json!({ "type": "string", "format": "date-time" }),
+        Type::File => json!({ "type": "string", "format": "binary" }),
+        Type::Array(inner) => json!({ "type": "array", "items": type_schema(inner) }),
+        Type::Dictionary(inner) => json!({ "type": "object", "additionalProperties": type_schema(inner) }),
+        Type::Tuple(types) => json!({ "type": "array", "prefixItems": types.iter().map(type_schema).collect::<Vec<_>>() }),
+        Type::Optional(inner) => json!({ "anyOf": [type_schema(inner), { "type": "null" }] }),
+        Type::Union(types) => json!({ "anyOf": types.iter().map(type_schema).collect::<Vec<_>>() }),
+        Type::EnumVariant(_) => json!({ "type": "string" }),
+        Type::ModelObject(reference) => schema_ref(&reference.string_path().join(".")),
+        Type::InterfaceObject(reference, _) => schema_ref(&reference.string_path().join(".")),
+        _ => json!({}),
+    }
+}
+
+fn error_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "error": {
+                "type": "object",
+                "properties": {
+                    "type": { "type": "string" },
+                    "message": { "type": "string" },
+                    "requestId": { "type": "string" },
+                    "errors": {
+                        "type": "object",
+                        "description": "Messages by the key path of the invalid input",
+                        "additionalProperties": { "type": "string" },
+                    },
+                },
+                "required": ["type", "message"],
+            },
+        },
+        "required": ["error"],
+    })
+}
+
+fn responses(success: JsonValue) -> JsonValue {
+    json!({
+        "200": {
+            "description": "Success",
+            "content": {
+                "application/json": {
+                    "schema": success,
+                },
+            },
+        },
+        "default": { "$ref": "#/components/responses/Error" },
+    })
+}
+
+fn query_parameter(schema: JsonValue) -> JsonValue {
+    json!({
+        "name": "q",
+        "in": "query",
+        "required": false,
+        "description": "The handler input as JSON, plain query parameters are merged into it",
+        "content": {
+            "application/json": {
+                "schema": schema,
+            },
+        },
+    })
+}
+
+fn insert_operation(paths: &mut Map<String, JsonValue>, path: &str, method: &str, operation: JsonValue) {
+    let item = paths.entry(path.to_owned()).or_insert_with(|| json!({}));
+    item[method] = operation;
+}
+
+fn route_path(path_prefix: &str, route: &[String], name: &str) -> String {
+    let mut path = path_prefix.to_owned();
+    for segment in route.iter().map(|segment| segment.as_str()).chain([name]) {
+        if !segment.is_empty() {
+            path.push('/');
+            path.push_str(segment);
+        }
+    }
+    if path.is_empty() { "/".to_owned() } else { path }
+}
+
+/// Convert `:name` and `*name` segments to OpenAPI templates.
+fn path_parameters(path: &str) -> (String, Vec<JsonValue>) {
+    let mut parameters = vec![];
+    let segments = path.split('/').map(|segment| {
+        match segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+            Some(name) if !name.is_empty() => {
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }));
+                format!("{{{}}}", name)
+            }
+            _ => segment.to_owned(),
+        }
+    }).collect::<Vec<_>>();
+    (segments.join("/"), parameters)
+}
+
+fn schema_name(namespace_path: &[String], name: &str) -> String {
+    let mut names = namespace_path.to_vec();
+    names.push(name.to_owned());
+    names.join(".")
+}
+
+fn schema_ref(name: &str) -> JsonValue {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn tag(route: &[String]) -> String {
+    if route.is_empty() { "main".to_owned() } else { route.join(".") }
+}
@@ -0,0 +1,236 @@
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use bytes::Bytes;
+use chrono::Local;
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::header::CONTENT_LENGTH;
+use hyper::http::HeaderMap;
+use hyper::Version;
+use serde_json::json;
+use teo_result::Result;
+use teo_runtime::namespace::Namespace;
+use teo_runtime::request::Request;
+use crate::server::response::HyperResponseBody;
+use crate::server::utils::{matched_handler, MatchedHandler};
+
+#[derive(Debug, Clone, Default)]
+pub struct AccessLogConfig {
+    pub format: AccessLogFormat,
+    pub output: AccessLogOutput,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AccessLogFormat {
+    /// The common log format followed by the request size, the matched handler and the latency,
+    /// sizes which aren't known are logged as `-`
+    #[default]
+    Common,
+    /// One JSON object per line
+    JsonLines,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AccessLogOutput {
+    #[default]
+    Stdout,
+    /// Append to this file, it's created when missing
+    File(PathBuf),
+}
+
+/// Lines waiting for the writer thread, more are dropped rather than holding up requests.
+const ACCESS_LOG_BUFFER: usize = 8192;
+
+/// Formats access log lines and hands them to a thread which does the blocking writes.
+#[derive(Debug)]
+pub(super) struct AccessLogger {
+    format: AccessLogFormat,
+    sender: SyncSender<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// What is known about a request before it's handed to the request middleware stack.
+pub(super) struct AccessLogRequest {
+    client_ip: Option<IpAddr>,
+    method: String,
+    path: String,
+    version: Version,
+    size: Option<u64>,
+}
+
+impl AccessLogRequest {
+    pub(super) fn new(hyper_request: &hyper::Request<Incoming>, peer_addr: Option<SocketAddr>) -> Self {
+        Self {
+            client_ip: peer_addr.map(|addr| addr.ip()),
+            method: hyper_request.method().to_string(),
+            path: hyper_request.uri().path().to_owned(),
+            version: hyper_request.version(),
+            size: content_length(hyper_request.headers()).or(hyper_request.body().size_hint().exact()),
+        }
+    }
+}
+
+impl AccessLogger {
+
+    pub(super) fn new(config: &AccessLogConfig) -> Result<Self> {
+        let mut writer: Box<dyn Write + Send> = match &config.output {
+            AccessLogOutput::Stdout => Box::new(std::io::stdout()),
+            AccessLogOutput::File(path) => Box::new(BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?)),
+        };
+        let (sender, receiver) = sync_channel::<String>(ACCESS_LOG_BUFFER);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let writer_dropped = dropped.clone();
+        // the thread ends once the server and every response body holding a sender are gone
+        std::thread::Builder::new().name("teo-access-log".to_owned()).spawn(move || {
+            while let Ok(line) = receiver.recv() {
+                let dropped = writer_dropped.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    eprintln!("Access log fell behind, {} lines were dropped", dropped);
+                }
+                let mut result = writeln!(writer, "{}", line);
+                // flush whenever the queue runs empty so lines show up without delay
+                while result.is_ok() {
+                    let Ok(line) = receiver.try_recv() else {
+                        break;
+                    };
+                    result = writeln!(writer, "{}", line);
+                }
+                if let Err(err) = result.and_then(|_| writer.flush()) {
+                    eprintln!("Error writing access log: {:?}", err);
+                }
+            }
+        })?;
+        Ok(Self { format: config.format, sender, dropped })
+    }
+
+    /// Start the log entry of a response, it's written when the response body is fully sent or
+    /// dropped, so the latency and size cover streamed, file and compressed bodies.
+    pub(super) fn pending(self: &Arc<Self>, main_namespace: &Namespace, access_log_request: AccessLogRequest, request: &Request, response: &hyper::Response<HyperResponseBody>, started_at: Instant) -> PendingAccessLog {
+        PendingAccessLog {
+            logger: self.clone(),
+            request: access_log_request,
+            handler: matched_handler(main_namespace, request),
+            status: response.status().as_u16(),
+            started_at,
+        }
+    }
+
+    fn write(&self, line: String) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(line) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub(super) struct PendingAccessLog {
+    logger: Arc<AccessLogger>,
+    request: AccessLogRequest,
+    handler: Option<MatchedHandler>,
+    status: u16,
+    started_at: Instant,
+}
+
+impl PendingAccessLog {
+
+    fn finish(self, response_size: u64) {
+        let latency = self.started_at.elapsed();
+        let access_log_request = self.request;
+        let line = match self.logger.format {
+            AccessLogFormat::Common => format!(
+                "{} - - [{}] \"{} {} {:?}\" {} {} {} \"{}\" {:.3}ms",
+                access_log_request.client_ip.map_or("-".to_owned(), |ip| ip.to_string()),
+                Local::now().format("%d/%b/%Y:%H:%M:%S %z"),
+                access_log_request.method,
+                access_log_request.path,
+                access_log_request.version,
+                self.status,
+                response_size,
+                access_log_request.size.map_or("-".to_owned(), |size| size.to_string()),
+                self.handler.as_ref().map_or("-".to_owned(), |handler| {
+                    let mut names = handler.namespace.clone();
+                    names.extend(handler.model.clone());
+                    names.push(handler.action.clone());
+                    names.join(".")
+                }),
+                latency.as_secs_f64() * 1000.0,
+            ),
+            AccessLogFormat::JsonLines => json!({
+                "time": Local::now().to_rfc3339(),
+                "clientIp": access_log_request.client_ip.map(|ip| ip.to_string()),
+                "method": access_log_request.method,
+                "path": access_log_request.path,
+                "version": format!("{:?}", access_log_request.version),
+                "namespace": self.handler.as_ref().map(|handler| handler.namespace.clone()),
+                "model": self.handler.as_ref().and_then(|handler| handler.model.clone()),
+                "action": self.handler.as_ref().map(|handler| handler.action.clone()),
+                "status": self.status,
+                "latencyMs": latency.as_secs_f64() * 1000.0,
+                "requestSize": access_log_request.size,
+                "responseSize": response_size,
+            }).to_string(),
+        };
+        self.logger.write(line);
+    }
+}
+
+/// A response body which writes its access log entry once it's sent, with the bytes sent so far
+/// when the client goes away early.
+pub struct AccessLoggedBody {
+    inner: Pin<Box<HyperResponseBody>>,
+    pending: Option<PendingAccessLog>,
+    sent: u64,
+}
+
+impl AccessLoggedBody {
+    pub(super) fn new(inner: HyperResponseBody, pending: Option<PendingAccessLog>) -> Self {
+        Self { inner: Box::pin(inner), pending, sent: 0 }
+    }
+
+    fn finish(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.finish(self.sent);
+        }
+    }
+}
+
+impl Body for AccessLoggedBody {
+    type Data = Bytes;
+    type Error = <HyperResponseBody as Body>::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<std::result::Result<Frame<Self::Data>, Self::Error>>> {
+        let polled = self.inner.as_mut().poll_frame(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(frame))) => if let Some(data) = frame.data_ref() {
+                self.sent += data.len() as u64;
+            },
+            Poll::Ready(_) => self.finish(),
+            Poll::Pending => (),
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for AccessLoggedBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+pub(super) fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
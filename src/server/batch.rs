@@ -0,0 +1,238 @@
+//! The batch endpoint, running several actions in one transaction.
+//!
+//! Items are built with `Request::new_for_test`, the only constructor of the runtime which takes a
+//! body that's already in memory and a transaction ctx. So an item carries its body as test bytes
+//! instead of an incoming stream, and has no connection of its own: it can't be upgraded to a
+//! WebSocket, and code reading `take_incoming()` directly sees no body.
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::http::HeaderMap;
+use hyper::{Method, Version};
+use serde_json::{json, Value as JsonValue};
+use teo_result::{Error, Result};
+use teo_runtime::connection::transaction;
+use teo_runtime::request::Request;
+use teo_runtime::response::body::BodyInner;
+use teo_runtime::response::Response;
+use crate::server::parse_body::parse_json_body;
+use crate::server::request_id::{request_id, REQUEST_ID_HEADER};
+use crate::server::server::Server;
+use crate::server::timeout::gateway_timeout;
+use crate::server::utils::remove_path_prefix;
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// Path of the batch endpoint, after the server path prefix
+    pub path: String,
+    /// Maximum number of items in one batch
+    pub max_items: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            path: "/batch".to_owned(),
+            max_items: 100,
+        }
+    }
+}
+
+const BATCH_REQUEST_ID_KEY: &str = "batchRequestId";
+
+/// The ID of the batch request an item belongs to, also readable from handlers as the
+/// `batchRequestId` local value.
+pub fn batch_request_id(request: &Request) -> Option<String> {
+    request.local_values().get(BATCH_REQUEST_ID_KEY).ok().flatten()
+}
+
+/// One call of a batch, addressed like a regular request so it's dispatched like one.
+#[derive(Debug, Clone)]
+struct BatchItem {
+    method: Method,
+    path: String,
+    args: JsonValue,
+}
+
+pub(super) fn is_batch_request(config: &BatchConfig, request: &Request, path_prefix: Option<&String>) -> bool {
+    request.method() == Method::POST && remove_path_prefix(request.path(), path_prefix) == config.path
+}
+
+/// Run the items of a batch in order in one transaction, the first failure rolls back all of them.
+///
+/// Every item goes through the request middleware stack on its own with the headers of the batch
+/// request, except for the request ID. Items get IDs of their own and the ID of the batch as
+/// `batchRequestId`. An object like `{ "$ref": "0.data.id" }` in the args of an item is replaced with the
+/// value at that path in the response of an earlier item.
+pub(super) async fn process_batch(server: &Server, config: &BatchConfig, request: &Request) -> Result<Response> {
+    let limits = server.config().limits.default.clone();
+    let body = if let Some(incoming) = request.take_incoming() {
+        parse_json_body(incoming, &limits).await?
+    } else if let Some(incoming_full_bytes) = request.take_incoming_bytes_for_test() {
+        parse_json_body(incoming_full_bytes, &limits).await?
+    } else {
+        return Err(Error::internal_server_error_message("HTTP body is taken"));
+    };
+    let path_prefix = server.app.compiled_main_namespace().server().unwrap().path_prefix.clone();
+    let items = batch_items(config, &body, path_prefix.as_ref())?;
+    let template = request.clone_hyper_request_for_file_processing();
+    let mut headers = template.headers().clone();
+    headers.remove(REQUEST_ID_HEADER);
+    let batch_id = request_id(request);
+    let version = template.version();
+    let timeout = server.config().timeouts.default;
+    let server = server.clone();
+    let transaction_ctx = request.transaction_ctx();
+    let run = transaction_ctx.run_transaction(move |transaction_ctx: transaction::Ctx| {
+        let server = server.clone();
+        let items = items.clone();
+        let headers = headers.clone();
+        let batch_id = batch_id.clone();
+        async move {
+            let mut results = vec![];
+            for (index, mut item) in items.into_iter().enumerate() {
+                resolve_references(&mut item.args, &results).map_err(|error| item_error(index, error))?;
+                let hyper_request = item_request(&item, &headers, version)?;
+                // items share the transaction of the batch, their bodies are already in memory and
+                // `new_for_test` is the runtime's only constructor taking a body without a connection
+                let item_request = Request::new_for_test(hyper_request, transaction_ctx.clone());
+                if let Some(batch_id) = batch_id.as_ref() {
+                    item_request.local_values().insert(BATCH_REQUEST_ID_KEY, batch_id.clone());
+                }
+                let response = Box::pin(server.process_request(item_request)).await.map_err(|error| item_error(index, error))?;
+                results.push(response_json(&response).map_err(|error| item_error(index, error))?);
+            }
+            Ok(results)
+        }
+    });
+    // the deadline covers the whole batch, item handlers keep their own on top
+    let results = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, run).await {
+            Ok(results) => results?,
+            Err(_) => {
+                if let Err(err) = transaction_ctx.abort().await {
+                    eprintln!("Error rolling back timed out batch: {:?}", err);
+                }
+                return Err(gateway_timeout(timeout));
+            }
+        },
+        None => run.await?,
+    };
+    Response::string(json!({ "data": results }).to_string(), "application/json")
+}
+
+fn batch_items(config: &BatchConfig, body: &JsonValue, path_prefix: Option<&String>) -> Result<Vec<BatchItem>> {
+    let Some(items) = body.get("items").and_then(|items| items.as_array()) else {
+        return Err(Error::invalid_request_message("expect `items` to be an array"));
+    };
+    if items.len() > config.max_items {
+        return Err(Error::invalid_request_message(format!("batch exceeds the limit of {} items", config.max_items)));
+    }
+    let prefix = path_prefix.map_or("", |prefix| prefix.trim_end_matches('/'));
+    items.iter().enumerate().map(|(index, item)| {
+        let args = item.get("args").cloned().unwrap_or(json!({}));
+        let (method, path) = if let (Some(model), Some(action)) = (item.get("model").and_then(|m| m.as_str()), item.get("action").and_then(|a| a.as_str())) {
+            (Method::POST, format!("{}/{}/{}", prefix, model.split('.').collect::<Vec<_>>().join("/"), action))
+        } else {
+            let Some(path) = item.get("path").and_then(|p| p.as_str()) else {
+                return Err(Error::invalid_request_message(format!("batch item {} needs `model` and `action`, or `path`", index)));
+            };
+            let method = match item.get("method").and_then(|m| m.as_str()) {
+                Some(method) => match Method::from_bytes(method.to_uppercase().as_bytes()) {
+                    Ok(method) => method,
+                    Err(_) => return Err(Error::invalid_request_message(format!("batch item {} has an invalid method", index))),
+                },
+                None => Method::POST,
+            };
+            (method, format!("{}/{}", prefix, path.trim_start_matches('/')))
+        };
+        // compared like the batch request itself, so spellings like `batch` are caught too
+        if method == Method::POST && remove_path_prefix(&path, path_prefix) == config.path {
+            return Err(Error::invalid_request_message(format!("batch item {} cannot be a batch", index)));
+        }
+        Ok(BatchItem { method, path, args })
+    }).collect()
+}
+
+fn item_request(item: &BatchItem, headers: &HeaderMap, version: Version) -> Result<hyper::Request<Full<Bytes>>> {
+    let args = item.args.to_string();
+    // GET and DELETE handlers read their input from the query string
+    let (uri, body) = if item.method == Method::GET || item.method == Method::DELETE {
+        (format!("{}?q={}", item.path, form_urlencoded::byte_serialize(args.as_bytes()).collect::<String>()), String::new())
+    } else {
+        (item.path.clone(), args)
+    };
+    let mut builder = hyper::Request::builder().method(item.method.clone()).uri(uri).version(version);
+    for (name, value) in headers {
+        if name != CONTENT_LENGTH && name != CONTENT_TYPE {
+            builder = builder.header(name, value);
+        }
+    }
+    match builder.header(CONTENT_TYPE, "application/json").body(Full::new(Bytes::from(body))) {
+        Ok(hyper_request) => Ok(hyper_request),
+        Err(_) => Err(Error::invalid_request_message(format!("invalid batch item path: {}", item.path))),
+    }
+}
+
+fn response_json(response: &Response) -> Result<JsonValue> {
+    if response.code() >= 400 {
+        let mut error = Error::new(format!("responded with status {}", response.code()));
+        error.code = response.code();
+        return Err(error);
+    }
+    match response.body().inner.as_ref() {
+        BodyInner::Empty => Ok(JsonValue::Null),
+        BodyInner::String(content) => Ok(serde_json::from_str(content).unwrap_or_else(|_| JsonValue::String(content.to_string()))),
+        BodyInner::Teon(value) => match JsonValue::try_from(value) {
+            Ok(json_value) => Ok(json_value),
+            Err(_) => Err(Error::internal_server_error_message("cannot convert teon value to json")),
+        },
+        BodyInner::File(_) => Err(Error::invalid_request_message("file responses cannot be batched")),
+    }
+}
+
+fn resolve_references(value: &mut JsonValue, results: &[JsonValue]) -> Result<()> {
+    if let Some(reference) = value.as_object().filter(|object| object.len() == 1).and_then(|object| object.get("$ref")).and_then(|r| r.as_str()) {
+        *value = lookup_reference(reference, results)?.clone();
+        return Ok(());
+    }
+    match value {
+        JsonValue::Object(object) => for child in object.values_mut() {
+            resolve_references(child, results)?;
+        },
+        JsonValue::Array(array) => for child in array.iter_mut() {
+            resolve_references(child, results)?;
+        },
+        _ => (),
+    }
+    Ok(())
+}
+
+fn lookup_reference<'a>(reference: &str, results: &'a [JsonValue]) -> Result<&'a JsonValue> {
+    let mut segments = reference.split('.');
+    let index = segments.next().and_then(|index| index.parse::<usize>().ok());
+    let Some(mut value) = index.and_then(|index| results.get(index)) else {
+        return Err(Error::invalid_request_message(format!("reference `{}` doesn't point to an earlier item", reference)));
+    };
+    for segment in segments {
+        let next = match value {
+            JsonValue::Array(array) => segment.parse::<usize>().ok().and_then(|index| array.get(index)),
+            JsonValue::Object(object) => object.get(segment),
+            _ => None,
+        };
+        value = match next {
+            Some(next) => next,
+            None => return Err(Error::invalid_request_message(format!("reference `{}` doesn't exist", reference))),
+        };
+    }
+    Ok(value)
+}
+
+/// Keep the status of the failed item and say which one failed.
+fn item_error(index: usize, error: Error) -> Error {
+    let mut wrapped = Error::new(format!("batch item {} failed: {}", index, error.message()));
+    wrapped.code = error.code;
+    wrapped.errors = error.errors;
+    wrapped
+}
@@ -0,0 +1,478 @@
+use std::io::Cursor;
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use ciborium::value::{Integer, Value as CborValue};
+use rmpv::Value as MsgPackValue;
+use serde_json::{Map as JsonMap, Number, Value as JsonValue};
+use teo_result::{Error, Result};
+use teo_runtime::value::Value;
+
+/// MessagePack extension type of the standard timestamp
+const MSGPACK_TIMESTAMP: i8 = -1;
+/// MessagePack extension type of a decimal, the payload is its UTF-8 decimal notation
+const MSGPACK_DECIMAL: i8 = 1;
+/// MessagePack extension type of a date, the payload is UTF-8 `YYYY-MM-DD`
+const MSGPACK_DATE: i8 = 2;
+/// MessagePack extension type of an object id, the payload is its 12 bytes
+const MSGPACK_OBJECT_ID: i8 = 3;
+
+/// CBOR tag of an RFC 3339 date time string
+const CBOR_DATE_TIME: u64 = 0;
+/// CBOR tag of seconds since the epoch
+const CBOR_EPOCH: u64 = 1;
+const CBOR_POSITIVE_BIGNUM: u64 = 2;
+const CBOR_NEGATIVE_BIGNUM: u64 = 3;
+/// CBOR tag of a decimal fraction `[exponent, mantissa]`
+const CBOR_DECIMAL_FRACTION: u64 = 4;
+/// CBOR tag of an identifier, used for object ids as 12 bytes
+const CBOR_IDENTIFIER: u64 = 39;
+/// CBOR tag of an RFC 3339 full-date string
+const CBOR_FULL_DATE: u64 = 1004;
+
+/// Largest decimal exponent accepted from a request, 10^1000 is beyond any database decimal
+const MAX_DECIMAL_EXPONENT: u64 = 1000;
+/// Largest bignum accepted from a request, 64 bytes hold up to 155 digits
+const MAX_BIGNUM_BYTES: usize = 64;
+
+/// A binary encoding of request and response bodies next to JSON.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryFormat {
+    MessagePack,
+    Cbor,
+}
+
+impl BinaryFormat {
+
+    pub fn from_content_type(essence: &str) -> Option<Self> {
+        match essence {
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(BinaryFormat::MessagePack),
+            "application/cbor" => Some(BinaryFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BinaryFormat::MessagePack => "application/msgpack",
+            BinaryFormat::Cbor => "application/cbor",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            BinaryFormat::MessagePack => "msgpack",
+            BinaryFormat::Cbor => "cbor",
+        }
+    }
+
+    /// Decode a body into the JSON value validation expects, tagged values become their JSON notation.
+    pub(super) fn decode(&self, body: &[u8]) -> Result<JsonValue> {
+        let invalid = || Error::invalid_request_message(format!("incorrect {} format", self.name()));
+        match self {
+            BinaryFormat::MessagePack => {
+                let value = rmpv::decode::read_value(&mut Cursor::new(body)).map_err(|_| invalid())?;
+                msgpack_to_json(value)
+            }
+            BinaryFormat::Cbor => {
+                let value: CborValue = ciborium::de::from_reader(Cursor::new(body)).map_err(|_| invalid())?;
+                cbor_to_json(value)
+            }
+        }
+    }
+
+    pub(super) fn encode(&self, value: &Value) -> Result<Bytes> {
+        let mut buffer = vec![];
+        let result = match self {
+            BinaryFormat::MessagePack => rmpv::encode::write_value(&mut buffer, &teon_to_msgpack(value)?).map_err(|err| err.to_string()),
+            BinaryFormat::Cbor => ciborium::ser::into_writer(&teon_to_cbor(value)?, &mut buffer).map_err(|err| err.to_string()),
+        };
+        match result {
+            Ok(()) => Ok(Bytes::from(buffer)),
+            Err(err) => Err(Error::internal_server_error_message(format!("cannot encode {} response: {}", self.name(), err))),
+        }
+    }
+}
+
+/// The binary format preferred by `Accept`, `None` means JSON.
+pub(super) fn negotiate(accept: &str) -> Option<BinaryFormat> {
+    let mut best: Option<(Option<BinaryFormat>, f32)> = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let essence = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+            .find_map(|quality| quality.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let format = match essence.as_str() {
+            "application/json" | "application/*" | "*/*" => None,
+            essence => match BinaryFormat::from_content_type(essence) {
+                Some(format) => Some(format),
+                None => continue,
+            },
+        };
+        // the first of equally preferred types wins
+        if quality > 0.0 && best.map_or(true, |(_, best_quality)| quality > best_quality) {
+            best = Some((format, quality));
+        }
+    }
+    best.and_then(|(format, _)| format)
+}
+
+fn msgpack_to_json(value: MsgPackValue) -> Result<JsonValue> {
+    Ok(match value {
+        MsgPackValue::Nil => JsonValue::Null,
+        MsgPackValue::Boolean(b) => JsonValue::Bool(b),
+        MsgPackValue::Integer(i) => match (i.as_i64(), i.as_u64()) {
+            (Some(i), _) => JsonValue::from(i),
+            (None, Some(u)) => JsonValue::from(u),
+            _ => unreachable!(),
+        },
+        MsgPackValue::F32(f) => float_to_json(f as f64)?,
+        MsgPackValue::F64(f) => float_to_json(f)?,
+        MsgPackValue::String(s) => match s.into_str() {
+            Some(s) => JsonValue::String(s),
+            None => return Err(Error::invalid_request_message("msgpack strings must be valid UTF-8")),
+        },
+        MsgPackValue::Binary(_) => return Err(Error::invalid_request_message("msgpack binary values are not supported")),
+        MsgPackValue::Array(array) => JsonValue::Array(array.into_iter().map(msgpack_to_json).collect::<Result<_>>()?),
+        MsgPackValue::Map(entries) => {
+            let mut map = JsonMap::new();
+            for (key, value) in entries {
+                let MsgPackValue::String(key) = key else {
+                    return Err(Error::invalid_request_message("msgpack map keys must be strings"))
+                };
+                let Some(key) = key.into_str() else {
+                    return Err(Error::invalid_request_message("msgpack strings must be valid UTF-8"))
+                };
+                map.insert(key, msgpack_to_json(value)?);
+            }
+            JsonValue::Object(map)
+        }
+        MsgPackValue::Ext(MSGPACK_TIMESTAMP, data) => JsonValue::String(date_time_string(msgpack_timestamp(&data)?)),
+        MsgPackValue::Ext(MSGPACK_DECIMAL, data) | MsgPackValue::Ext(MSGPACK_DATE, data) => match String::from_utf8(data) {
+            Ok(s) => JsonValue::String(s),
+            Err(_) => return Err(Error::invalid_request_message("msgpack extension payload must be valid UTF-8")),
+        },
+        MsgPackValue::Ext(MSGPACK_OBJECT_ID, data) => JsonValue::String(object_id_hex(&data)?),
+        MsgPackValue::Ext(ext, _) => return Err(Error::invalid_request_message(format!("unknown msgpack extension type {}", ext))),
+    })
+}
+
+fn msgpack_timestamp(data: &[u8]) -> Result<DateTime<Utc>> {
+    let (seconds, nanoseconds) = match data.len() {
+        4 => (u32::from_be_bytes(data.try_into().unwrap()) as i64, 0),
+        8 => {
+            let value = u64::from_be_bytes(data.try_into().unwrap());
+            ((value & 0x3_ffff_ffff) as i64, (value >> 34) as u32)
+        }
+        12 => (i64::from_be_bytes(data[4..].try_into().unwrap()), u32::from_be_bytes(data[..4].try_into().unwrap())),
+        _ => return Err(Error::invalid_request_message("invalid msgpack timestamp")),
+    };
+    match DateTime::from_timestamp(seconds, nanoseconds) {
+        Some(date_time) => Ok(date_time),
+        None => Err(Error::invalid_request_message("invalid msgpack timestamp")),
+    }
+}
+
+fn cbor_to_json(value: CborValue) -> Result<JsonValue> {
+    Ok(match value {
+        CborValue::Null => JsonValue::Null,
+        CborValue::Bool(b) => JsonValue::Bool(b),
+        CborValue::Integer(i) => integer_to_json(i)?,
+        CborValue::Float(f) => float_to_json(f)?,
+        CborValue::Text(s) => JsonValue::String(s),
+        CborValue::Bytes(_) => return Err(Error::invalid_request_message("cbor byte strings are only supported as object ids")),
+        CborValue::Array(array) => JsonValue::Array(array.into_iter().map(cbor_to_json).collect::<Result<_>>()?),
+        CborValue::Map(entries) => {
+            let mut map = JsonMap::new();
+            for (key, value) in entries {
+                let CborValue::Text(key) = key else {
+                    return Err(Error::invalid_request_message("cbor map keys must be text"))
+                };
+                map.insert(key, cbor_to_json(value)?);
+            }
+            JsonValue::Object(map)
+        }
+        CborValue::Tag(CBOR_DATE_TIME, value) | CborValue::Tag(CBOR_FULL_DATE, value) => match *value {
+            CborValue::Text(s) => JsonValue::String(s),
+            _ => return Err(Error::invalid_request_message("invalid cbor date")),
+        },
+        CborValue::Tag(CBOR_EPOCH, value) => {
+            let seconds = match *value {
+                CborValue::Integer(i) => i128::from(i) as f64,
+                CborValue::Float(f) => f,
+                _ => return Err(Error::invalid_request_message("invalid cbor epoch time")),
+            };
+            match DateTime::from_timestamp(seconds.floor() as i64, (seconds.fract() * 1e9) as u32) {
+                Some(date_time) => JsonValue::String(date_time_string(date_time)),
+                None => return Err(Error::invalid_request_message("invalid cbor epoch time")),
+            }
+        }
+        CborValue::Tag(CBOR_POSITIVE_BIGNUM | CBOR_NEGATIVE_BIGNUM, _) => JsonValue::String(cbor_integer_digits(value)?),
+        CborValue::Tag(CBOR_DECIMAL_FRACTION, value) => {
+            let CborValue::Array(parts) = *value else {
+                return Err(Error::invalid_request_message("invalid cbor decimal fraction"))
+            };
+            let [exponent, mantissa]: [CborValue; 2] = match parts.try_into() {
+                Ok(parts) => parts,
+                Err(_) => return Err(Error::invalid_request_message("invalid cbor decimal fraction")),
+            };
+            let CborValue::Integer(exponent) = exponent else {
+                return Err(Error::invalid_request_message("invalid cbor decimal fraction"))
+            };
+            let Ok(exponent) = i64::try_from(i128::from(exponent)) else {
+                return Err(Error::invalid_request_message("cbor decimal exponent is out of range"))
+            };
+            JsonValue::String(decimal_string(&cbor_integer_digits(mantissa)?, exponent)?)
+        }
+        CborValue::Tag(CBOR_IDENTIFIER, value) => match *value {
+            CborValue::Bytes(bytes) => JsonValue::String(object_id_hex(&bytes)?),
+            _ => return Err(Error::invalid_request_message("invalid cbor object id")),
+        },
+        // tags without a meaning here leave their content as it is
+        CborValue::Tag(_, value) => cbor_to_json(*value)?,
+        _ => return Err(Error::invalid_request_message("unsupported cbor value")),
+    })
+}
+
+fn integer_to_json(integer: Integer) -> Result<JsonValue> {
+    let integer = i128::from(integer);
+    if let Ok(i) = i64::try_from(integer) {
+        Ok(JsonValue::from(i))
+    } else if let Ok(u) = u64::try_from(integer) {
+        Ok(JsonValue::from(u))
+    } else {
+        Ok(JsonValue::String(integer.to_string()))
+    }
+}
+
+/// Digits of an integer or bignum, with a leading `-` when it's negative.
+fn cbor_integer_digits(value: CborValue) -> Result<String> {
+    match value {
+        CborValue::Integer(i) => Ok(i128::from(i).to_string()),
+        CborValue::Tag(tag @ (CBOR_POSITIVE_BIGNUM | CBOR_NEGATIVE_BIGNUM), value) => {
+            let CborValue::Bytes(bytes) = *value else {
+                return Err(Error::invalid_request_message("invalid cbor bignum"));
+            };
+            // the conversion to digits is quadratic in the length
+            if bytes.len() > MAX_BIGNUM_BYTES {
+                return Err(Error::invalid_request_message(format!("cbor bignum exceeds {} bytes", MAX_BIGNUM_BYTES)));
+            }
+            // a negative bignum encodes -1 - n
+            let digits = if tag == CBOR_NEGATIVE_BIGNUM { digits_add_one(&bytes_to_digits(&bytes)) } else { bytes_to_digits(&bytes) };
+            Ok(if tag == CBOR_NEGATIVE_BIGNUM { format!("-{}", digits) } else { digits })
+        }
+        _ => Err(Error::invalid_request_message("invalid cbor integer")),
+    }
+}
+
+fn teon_to_msgpack(value: &Value) -> Result<MsgPackValue> {
+    Ok(match value {
+        Value::Null => MsgPackValue::Nil,
+        Value::Bool(b) => MsgPackValue::Boolean(*b),
+        Value::Int(i) => MsgPackValue::from(*i),
+        Value::Int64(i) => MsgPackValue::from(*i),
+        Value::Float32(f) => MsgPackValue::F32(*f),
+        Value::Float(f) => MsgPackValue::F64(*f),
+        Value::String(s) => MsgPackValue::from(s.as_str()),
+        Value::Decimal(decimal) => MsgPackValue::Ext(MSGPACK_DECIMAL, decimal.to_string().into_bytes()),
+        Value::Date(date) => MsgPackValue::Ext(MSGPACK_DATE, date_string(date).into_bytes()),
+        Value::DateTime(date_time) => {
+            let mut data = date_time.timestamp_subsec_nanos().to_be_bytes().to_vec();
+            data.extend(date_time.timestamp().to_be_bytes());
+            MsgPackValue::Ext(MSGPACK_TIMESTAMP, data)
+        }
+        Value::ObjectId(object_id) => MsgPackValue::Ext(MSGPACK_OBJECT_ID, object_id.bytes().to_vec()),
+        Value::Array(array) => MsgPackValue::Array(array.iter().map(teon_to_msgpack).collect::<Result<_>>()?),
+        Value::Dictionary(map) => MsgPackValue::Map(map.iter().map(|(key, value)| Ok((MsgPackValue::from(key.as_str()), teon_to_msgpack(value)?))).collect::<Result<_>>()?),
+        // the remaining values have no binary counterpart, they're written like in JSON
+        value => json_to_msgpack(&teon_to_json(value)?),
+    })
+}
+
+fn json_to_msgpack(value: &JsonValue) -> MsgPackValue {
+    match value {
+        JsonValue::Null => MsgPackValue::Nil,
+        JsonValue::Bool(b) => MsgPackValue::Boolean(*b),
+        JsonValue::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => MsgPackValue::from(i),
+            (None, Some(u)) => MsgPackValue::from(u),
+            _ => MsgPackValue::F64(n.as_f64().unwrap_or_default()),
+        },
+        JsonValue::String(s) => MsgPackValue::from(s.as_str()),
+        JsonValue::Array(array) => MsgPackValue::Array(array.iter().map(json_to_msgpack).collect()),
+        JsonValue::Object(map) => MsgPackValue::Map(map.iter().map(|(key, value)| (MsgPackValue::from(key.as_str()), json_to_msgpack(value))).collect()),
+    }
+}
+
+fn teon_to_cbor(value: &Value) -> Result<CborValue> {
+    Ok(match value {
+        Value::Null => CborValue::Null,
+        Value::Bool(b) => CborValue::Bool(*b),
+        Value::Int(i) => CborValue::Integer((*i).into()),
+        Value::Int64(i) => CborValue::Integer((*i).into()),
+        Value::Float32(f) => CborValue::Float(*f as f64),
+        Value::Float(f) => CborValue::Float(*f),
+        Value::String(s) => CborValue::Text(s.clone()),
+        Value::Decimal(decimal) => {
+            let (mantissa, scale) = decimal.as_bigint_and_exponent();
+            CborValue::Tag(CBOR_DECIMAL_FRACTION, Box::new(CborValue::Array(vec![
+                CborValue::Integer((-scale).into()),
+                cbor_integer(&mantissa.to_string()),
+            ])))
+        }
+        Value::Date(date) => CborValue::Tag(CBOR_FULL_DATE, Box::new(CborValue::Text(date_string(date)))),
+        Value::DateTime(date_time) => CborValue::Tag(CBOR_DATE_TIME, Box::new(CborValue::Text(date_time_string(*date_time)))),
+        Value::ObjectId(object_id) => CborValue::Tag(CBOR_IDENTIFIER, Box::new(CborValue::Bytes(object_id.bytes().to_vec()))),
+        Value::Array(array) => CborValue::Array(array.iter().map(teon_to_cbor).collect::<Result<_>>()?),
+        Value::Dictionary(map) => CborValue::Map(map.iter().map(|(key, value)| Ok((CborValue::Text(key.clone()), teon_to_cbor(value)?))).collect::<Result<_>>()?),
+        // the remaining values have no binary counterpart, they're written like in JSON
+        value => json_to_cbor(&teon_to_json(value)?),
+    })
+}
+
+fn json_to_cbor(value: &JsonValue) -> CborValue {
+    match value {
+        JsonValue::Null => CborValue::Null,
+        JsonValue::Bool(b) => CborValue::Bool(*b),
+        JsonValue::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => CborValue::Integer(i.into()),
+            (None, Some(u)) => CborValue::Integer(u.into()),
+            _ => CborValue::Float(n.as_f64().unwrap_or_default()),
+        },
+        JsonValue::String(s) => CborValue::Text(s.clone()),
+        JsonValue::Array(array) => CborValue::Array(array.iter().map(json_to_cbor).collect()),
+        JsonValue::Object(map) => CborValue::Map(map.iter().map(|(key, value)| (CborValue::Text(key.clone()), json_to_cbor(value))).collect()),
+    }
+}
+
+/// An integer from its decimal digits, as a bignum when it doesn't fit 64 bits.
+fn cbor_integer(digits: &str) -> CborValue {
+    if let Ok(i) = digits.parse::<i64>() {
+        return CborValue::Integer(i.into());
+    }
+    match digits.strip_prefix('-') {
+        Some(digits) => CborValue::Tag(CBOR_NEGATIVE_BIGNUM, Box::new(CborValue::Bytes(digits_to_bytes(&digits_sub_one(digits))))),
+        None => CborValue::Tag(CBOR_POSITIVE_BIGNUM, Box::new(CborValue::Bytes(digits_to_bytes(digits)))),
+    }
+}
+
+fn teon_to_json(value: &Value) -> Result<JsonValue> {
+    match JsonValue::try_from(value) {
+        Ok(json_value) => Ok(json_value),
+        Err(_) => Err(Error::internal_server_error_message("cannot convert teon value to json")),
+    }
+}
+
+fn float_to_json(f: f64) -> Result<JsonValue> {
+    match Number::from_f64(f) {
+        Some(n) => Ok(JsonValue::Number(n)),
+        None => Err(Error::invalid_request_message("NaN and infinite numbers are not supported")),
+    }
+}
+
+fn date_string(date: &NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn date_time_string(date_time: DateTime<Utc>) -> String {
+    date_time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn object_id_hex(bytes: &[u8]) -> Result<String> {
+    if bytes.len() != 12 {
+        return Err(Error::invalid_request_message("an object id has 12 bytes"));
+    }
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// `mantissa * 10^exponent` in decimal notation.
+fn decimal_string(mantissa: &str, exponent: i64) -> Result<String> {
+    // the exponent is written out as zeros
+    if exponent.unsigned_abs() > MAX_DECIMAL_EXPONENT {
+        return Err(Error::invalid_request_message(format!("cbor decimal exponent exceeds {}", MAX_DECIMAL_EXPONENT)));
+    }
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", mantissa),
+    };
+    let scale = exponent.unsigned_abs() as usize;
+    if exponent >= 0 {
+        return Ok(format!("{}{}{}", sign, digits, "0".repeat(scale)));
+    }
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    Ok(format!("{}{}.{}", sign, integer, fraction))
+}
+
+/// Decimal digits of an unsigned big-endian integer.
+fn bytes_to_digits(bytes: &[u8]) -> String {
+    // little-endian base 10 digits
+    let mut digits: Vec<u8> = vec![0];
+    for byte in bytes {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            let value = *digit as u32 * 256 + carry;
+            *digit = (value % 10) as u8;
+            carry = value / 10;
+        }
+        while carry > 0 {
+            digits.push((carry % 10) as u8);
+            carry /= 10;
+        }
+    }
+    while digits.len() > 1 && digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits.iter().rev().map(|digit| (b'0' + digit) as char).collect()
+}
+
+/// Unsigned big-endian bytes of decimal digits.
+fn digits_to_bytes(digits: &str) -> Vec<u8> {
+    // little-endian base 256 bytes
+    let mut bytes: Vec<u8> = vec![];
+    for digit in digits.bytes() {
+        let mut carry = (digit - b'0') as u32;
+        for byte in bytes.iter_mut() {
+            let value = *byte as u32 * 10 + carry;
+            *byte = (value % 256) as u8;
+            carry = value / 256;
+        }
+        while carry > 0 {
+            bytes.push((carry % 256) as u8);
+            carry /= 256;
+        }
+    }
+    bytes.reverse();
+    bytes
+}
+
+fn digits_add_one(digits: &str) -> String {
+    let mut result: Vec<u8> = digits.bytes().rev().collect();
+    for digit in result.iter_mut() {
+        if *digit == b'9' {
+            *digit = b'0';
+        } else {
+            *digit += 1;
+            return result.iter().rev().map(|digit| *digit as char).collect();
+        }
+    }
+    result.push(b'1');
+    result.iter().rev().map(|digit| *digit as char).collect()
+}
+
+fn digits_sub_one(digits: &str) -> String {
+    let mut result: Vec<u8> = digits.bytes().rev().collect();
+    for digit in result.iter_mut() {
+        if *digit == b'0' {
+            *digit = b'9';
+        } else {
+            *digit -= 1;
+            break;
+        }
+    }
+    while result.len() > 1 && result.last() == Some(&b'0') {
+        result.pop();
+    }
+    result.iter().rev().map(|digit| *digit as char).collect()
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use hyper::header::{HeaderValue, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use teo_result::Result;
+use teo_runtime::request::Request;
+use crate::server::compression::ContentEncoding;
+use crate::server::utils::handler_key;
+
+const BUILTIN_ACTION_KEY: &str = "__teo_builtin_action";
+
+const READ_ACTIONS: [&str; 6] = ["findUnique", "findFirst", "findMany", "count", "aggregate", "groupBy"];
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// Add a strong `ETag` to successful read actions and answer matching `If-None-Match` with 304
+    pub etag: bool,
+    /// Caching headers by handler or model action, keyed by dotted handler path like `Post.findMany`
+    pub handlers: BTreeMap<String, CachePolicy>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            etag: true,
+            handlers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicy {
+    /// Sent as `Cache-Control`, like `private, max-age=60`
+    pub cache_control: Option<String>,
+    /// A `DateTime` field like `updatedAt`, the latest value in the returned data becomes `Last-Modified`
+    pub last_modified_field: Option<String>,
+}
+
+/// Mark a request dispatched to a builtin model action.
+pub(super) fn mark_builtin_action(request: &Request) {
+    request.local_objects().insert(BUILTIN_ACTION_KEY, true);
+}
+
+/// Validators of a JSON response body, computed before it's compressed.
+pub(super) struct Validators {
+    etag: Option<String>,
+    last_modified: Option<DateTime<Utc>>,
+    cache_control: Option<String>,
+}
+
+impl Validators {
+
+    pub(super) fn new(config: &CacheConfig, request: &Request, code: u16, json_value: &JsonValue, body: &Bytes) -> Option<Self> {
+        if code != 200 {
+            return None;
+        }
+        let handler_match = request.handler_match().ok()?;
+        let policy = config.handlers.get(&handler_key(&handler_match));
+        // custom handlers named like a read action aren't read actions
+        let read_action = request.local_objects().get::<bool>(BUILTIN_ACTION_KEY).is_some()
+            && READ_ACTIONS.contains(&handler_match.handler_name());
+        if policy.is_none() && !read_action {
+            return None;
+        }
+        let etag = config.etag.then(|| format!("{:x}", Sha256::digest(body))[..32].to_owned());
+        let last_modified = policy
+            .and_then(|policy| policy.last_modified_field.as_ref())
+            .and_then(|field| latest_date_time(json_value.get("data")?, field));
+        let cache_control = policy.and_then(|policy| policy.cache_control.clone());
+        Some(Self { etag, last_modified, cache_control })
+    }
+
+    /// Whether the client already has this representation, `If-None-Match` takes precedence.
+    pub(super) fn not_modified(&self, request: &Request, encoding: Option<ContentEncoding>) -> Result<bool> {
+        if let Some(if_none_match) = request.headers().get(IF_NONE_MATCH.as_str())?.map(|value| value.to_string()) {
+            let Some(etag) = self.etag(encoding) else {
+                return Ok(false);
+            };
+            return Ok(if_none_match.split(',').map(|tag| tag.trim().trim_start_matches("W/")).any(|tag| tag == "*" || tag == etag));
+        }
+        if let (Some(if_modified_since), Some(last_modified)) = (request.headers().get(IF_MODIFIED_SINCE.as_str())?.map(|value| value.to_string()), self.last_modified) {
+            if let Ok(since) = DateTime::parse_from_rfc2822(&if_modified_since) {
+                // HTTP dates have no fractional seconds
+                return Ok(last_modified.timestamp() <= since.timestamp());
+            }
+        }
+        Ok(false)
+    }
+
+    pub(super) fn apply<B>(&self, response: &mut hyper::Response<B>, encoding: Option<ContentEncoding>) -> Result<()> {
+        let headers = response.headers_mut();
+        if let Some(etag) = self.etag(encoding) {
+            headers.insert(ETAG, HeaderValue::try_from(etag)?);
+        }
+        if let Some(last_modified) = self.last_modified {
+            headers.insert(LAST_MODIFIED, HeaderValue::try_from(last_modified.format(HTTP_DATE_FORMAT).to_string())?);
+        }
+        if let Some(cache_control) = self.cache_control.as_ref() {
+            headers.insert(CACHE_CONTROL, HeaderValue::try_from(cache_control.as_str())?);
+        }
+        Ok(())
+    }
+
+    /// A strong tag is per representation, so compressed bodies get their own.
+    fn etag(&self, encoding: Option<ContentEncoding>) -> Option<String> {
+        let etag = self.etag.as_ref()?;
+        Some(match encoding {
+            Some(encoding) => format!("\"{}-{}\"", etag, encoding.as_str()),
+            None => format!("\"{}\"", etag),
+        })
+    }
+}
+
+fn latest_date_time(data: &JsonValue, field: &str) -> Option<DateTime<Utc>> {
+    let records = match data {
+        JsonValue::Array(records) => records.iter().collect(),
+        record => vec![record],
+    };
+    records.into_iter()
+        .filter_map(|record| record.get(field)?.as_str())
+        .filter_map(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .max()
+}
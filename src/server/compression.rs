@@ -0,0 +1,109 @@
+use std::io::Write;
+use bytes::Bytes;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl ContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Zstd => "zstd",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    /// Encodings the server may use, earlier ones win when the client weights them equally
+    pub encodings: Vec<ContentEncoding>,
+    /// Bodies smaller than this many bytes are sent uncompressed
+    pub min_size: usize,
+    /// Content types which are compressed, an entry ending with `/` matches a whole type like `text/`
+    pub content_types: Vec<String>,
+    /// Serve `.br` and `.gz` siblings of files when the client accepts them
+    pub precompressed: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            encodings: vec![ContentEncoding::Brotli, ContentEncoding::Zstd, ContentEncoding::Gzip],
+            min_size: 1024,
+            content_types: vec![
+                "application/json".to_owned(),
+                "text/".to_owned(),
+                "application/javascript".to_owned(),
+                "application/xml".to_owned(),
+            ],
+            precompressed: true,
+        }
+    }
+}
+
+impl CompressionConfig {
+
+    /// Pick the encoding with the highest weight in the `Accept-Encoding` header.
+    pub fn negotiate(&self, accept_encoding: &str) -> Option<ContentEncoding> {
+        let accepted: Vec<(&str, f32)> = accept_encoding.split(',').filter_map(|item| {
+            let mut parts = item.split(';');
+            let name = parts.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            let weight = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(1.0, |q| q.trim().parse::<f32>().unwrap_or(0.0));
+            Some((name, weight))
+        }).collect();
+        let wildcard = accepted.iter().find(|(name, _)| *name == "*").map(|(_, weight)| *weight);
+        let mut best: Option<(ContentEncoding, f32)> = None;
+        for encoding in &self.encodings {
+            // an explicit entry always overrides the wildcard
+            let weight = accepted.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(encoding.as_str()))
+                .map(|(_, weight)| *weight)
+                .or(wildcard);
+            if let Some(weight) = weight {
+                if weight > 0.0 && best.map_or(true, |(_, best_weight)| weight > best_weight) {
+                    best = Some((*encoding, weight));
+                }
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+
+    pub fn compresses_content_type(&self, content_type: &str) -> bool {
+        let essence = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        self.content_types.iter().any(|allowed| if allowed.ends_with('/') {
+            essence.starts_with(allowed.as_str())
+        } else {
+            essence == *allowed
+        })
+    }
+}
+
+pub(super) fn compress(body: &[u8], encoding: ContentEncoding) -> std::io::Result<Bytes> {
+    let compressed = match encoding {
+        ContentEncoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+        ContentEncoding::Brotli => {
+            // quality 5 keeps large payloads fast while still beating gzip
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+            encoder.write_all(body)?;
+            encoder.into_inner()
+        }
+        ContentEncoding::Zstd => zstd::bulk::compress(body, 3)?,
+    };
+    Ok(Bytes::from(compressed))
+}
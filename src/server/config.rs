@@ -0,0 +1,100 @@
+use crate::server::access_log::AccessLogConfig;
+use crate::server::batch::BatchConfig;
+use crate::server::cache::CacheConfig;
+use crate::server::compression::CompressionConfig;
+use crate::server::cors::CorsConfig;
+use crate::server::health::HealthConfig;
+use crate::server::limits::LimitsConfig;
+use crate::server::listener::ListenerConfig;
+use crate::server::metrics::MetricsConfig;
+use crate::server::openapi::OpenApiConfig;
+use crate::server::rate_limit::RateLimitConfig;
+use crate::server::shutdown::ShutdownConfig;
+use crate::server::timeout::TimeoutConfig;
+use crate::server::trace::TraceConfig;
+use crate::server::tls::TlsConfig;
+use crate::server::transaction::TransactionConfig;
+use teo_result::Result;
+
+/// Options of the HTTP server beyond the `bind` and `pathPrefix` of the schema's `server` block,
+/// set with `App::replace_server_config`. The `server` block is declared by the schema std lib of
+/// teo-parser and teo-runtime, which don't know protocol, TLS, CORS or caching keys yet, so these
+/// are set from code until they do.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub protocol: HttpProtocol,
+    /// Addresses to accept connections on, empty listens on the `bind` of the schema
+    pub listeners: Vec<ListenerConfig>,
+    /// Adopt the sockets systemd passes through `LISTEN_FDS` instead of binding listeners, off by default
+    pub socket_activation: bool,
+    pub tls: Option<TlsConfig>,
+    pub shutdown: ShutdownConfig,
+    pub limits: LimitsConfig,
+    pub timeouts: TimeoutConfig,
+    /// Builtin write actions run in a transaction by default
+    pub transaction: TransactionConfig,
+    pub compression: Option<CompressionConfig>,
+    /// ETags for read actions are on by default, other caching headers are set per handler
+    pub cache: CacheConfig,
+    pub cors: Option<CorsConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Access logging, off by default and silenced by `--silent`
+    pub access_log: Option<AccessLogConfig>,
+    /// Liveness and readiness probes, answered before any middleware runs, off by default
+    pub health: Option<HealthConfig>,
+    /// Prometheus metrics, off by default
+    pub metrics: Option<MetricsConfig>,
+    /// OpenTelemetry spans for every request, off by default
+    pub trace: Option<TraceConfig>,
+    /// Serve the OpenAPI document of the app, off by default
+    pub openapi: Option<OpenApiConfig>,
+    /// Endpoint running several actions in one transaction, off by default
+    pub batch: Option<BatchConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            protocol: HttpProtocol::default(),
+            listeners: vec![],
+            socket_activation: false,
+            tls: None,
+            shutdown: ShutdownConfig::default(),
+            limits: LimitsConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            transaction: TransactionConfig::default(),
+            compression: None,
+            cache: CacheConfig::default(),
+            cors: None,
+            rate_limit: None,
+            access_log: None,
+            health: None,
+            metrics: None,
+            trace: None,
+            openapi: None,
+            batch: None,
+        }
+    }
+}
+
+impl ServerConfig {
+
+    /// Reject combinations which can't be served safely.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(cors) = &self.cors {
+            cors.validate()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum HttpProtocol {
+    /// Serve HTTP/1.1 only
+    Http1,
+    /// Serve HTTP/2 only, over cleartext this requires prior knowledge
+    Http2,
+    /// Detect the protocol from the connection preface, HTTP/1.1 and HTTP/2 (h2c) are both accepted
+    #[default]
+    Auto,
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+use hyper::header::{HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY};
+use hyper::Method;
+use regex::Regex;
+use teo_result::{Error, Result};
+use teo_runtime::namespace::Namespace;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+
+#[derive(Debug, Clone)]
+pub enum CorsOrigin {
+    /// Any origin, answered with `*` and not allowed together with credentials
+    Any,
+    Exact(String),
+    /// Matches when the pattern spans the whole origin, `https://.*\.example\.com` doesn't allow
+    /// `https://a.example.com.evil.io`
+    Regex(Regex),
+}
+
+impl CorsOrigin {
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            CorsOrigin::Any => true,
+            CorsOrigin::Exact(exact) => exact == origin,
+            CorsOrigin::Regex(regex) => regex.find(origin).is_some_and(|found| found.start() == 0 && found.end() == origin.len()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub origins: Vec<CorsOrigin>,
+    /// Methods allowed across origins, `None` allows every method registered for the path
+    pub methods: Option<Vec<Method>>,
+    /// Request headers allowed in preflights, when empty the requested headers are allowed
+    pub headers: Vec<String>,
+    /// Response headers readable by the browser
+    pub expose_headers: Vec<String>,
+    pub credentials: bool,
+    pub max_age: Option<Duration>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            origins: vec![CorsOrigin::Any],
+            methods: None,
+            headers: vec![],
+            expose_headers: vec![],
+            credentials: false,
+            max_age: Some(Duration::from_secs(600)),
+        }
+    }
+}
+
+impl CorsConfig {
+
+    /// Browsers refuse credentialed responses for `*`, and echoing every origin back instead would
+    /// let any site make authenticated requests, so credentials need the origins listed.
+    pub fn validate(&self) -> Result<()> {
+        if self.credentials && self.origins.iter().any(|origin| matches!(origin, CorsOrigin::Any)) {
+            return Err(Error::new("cors: credentials cannot be allowed for any origin, list the allowed origins instead"));
+        }
+        Ok(())
+    }
+}
+
+const PREFLIGHT_METHODS: [Method; 5] = [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE];
+
+pub(super) fn is_preflight(request: &Request) -> Result<bool> {
+    Ok(request.method() == Method::OPTIONS && request.headers().get(ACCESS_CONTROL_REQUEST_METHOD.as_str())?.is_some())
+}
+
+/// The methods which have a handler registered for `path` and are allowed by the config.
+pub(super) fn allowed_methods(config: &CorsConfig, main_namespace: &Namespace, path: &str) -> Vec<Method> {
+    PREFLIGHT_METHODS.iter().filter(|&method| {
+        config.methods.as_ref().map_or(true, |methods| methods.contains(method))
+            && main_namespace.handler_map().match_all(method, path).is_some()
+    }).cloned().collect()
+}
+
+/// Answer a preflight request, the origin headers are added by `apply_cors_headers` like for any response.
+pub(super) fn preflight_response(config: &CorsConfig, request: &Request, methods: &[Method]) -> Result<Response> {
+    let response = Response::empty();
+    response.set_code(204);
+    let methods = methods.iter().map(|method| method.as_str()).collect::<Vec<_>>().join(", ");
+    response.headers().insert(ACCESS_CONTROL_ALLOW_METHODS.as_str(), methods)?;
+    let headers = if config.headers.is_empty() {
+        request.headers().get(ACCESS_CONTROL_REQUEST_HEADERS.as_str())?.map(|headers| headers.to_string())
+    } else {
+        Some(config.headers.join(", "))
+    };
+    if let Some(headers) = headers {
+        response.headers().insert(ACCESS_CONTROL_ALLOW_HEADERS.as_str(), headers)?;
+    }
+    if let Some(max_age) = config.max_age {
+        response.headers().insert(ACCESS_CONTROL_MAX_AGE.as_str(), max_age.as_secs().to_string())?;
+    }
+    Ok(response)
+}
+
+/// Add the CORS headers for the request origin, error responses included, so browsers can read them.
+pub(super) fn apply_cors_headers<B>(config: &CorsConfig, request: &Request, response: &mut hyper::Response<B>) -> Result<()> {
+    let wildcard = config.origins.iter().any(|allowed| matches!(allowed, CorsOrigin::Any));
+    let headers = response.headers_mut();
+    // the headers depend on the origin unless it's `*`, caches have to tell requests without one apart too
+    if !wildcard {
+        headers.append(VARY, HeaderValue::from_static("origin"));
+    }
+    let Some(origin) = request.headers().get(ORIGIN.as_str())?.map(|origin| origin.to_string()) else {
+        return Ok(());
+    };
+    if !config.origins.iter().any(|allowed| allowed.matches(&origin)) {
+        return Ok(());
+    }
+    if wildcard {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    } else {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::try_from(origin)?);
+    }
+    if config.credentials {
+        headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+    }
+    if !config.expose_headers.is_empty() {
+        headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::try_from(config.expose_headers.join(", "))?);
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::io;
+use std::pin::Pin;
+use async_compression::tokio::bufread::{BrotliDecoder, DeflateDecoder, GzipDecoder, ZlibDecoder};
+use bytes::Bytes;
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt};
+use http_body_util::{BodyStream, Limited, LengthLimitError, StreamBody};
+use hyper::body::{Body, Frame};
+use teo_result::{Error, Result};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio_util::io::{ReaderStream, StreamReader};
+use crate::server::limits::payload_too_large;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum RequestEncoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+/// A request body whose content codings are undone while it streams in.
+pub(super) type DecodedBody = StreamBody<BoxStream<'static, io::Result<Frame<Bytes>>>>;
+
+/// Parse the `Content-Encoding` header into the codings to undo, in the order they were applied.
+pub(super) fn request_encodings(content_encoding: &str) -> Result<Vec<RequestEncoding>> {
+    let mut encodings = vec![];
+    for coding in content_encoding.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        match coding.to_ascii_lowercase().as_str() {
+            "identity" => (),
+            "gzip" | "x-gzip" => encodings.push(RequestEncoding::Gzip),
+            "deflate" => encodings.push(RequestEncoding::Deflate),
+            "br" => encodings.push(RequestEncoding::Brotli),
+            _ => {
+                let mut error = Error::new(format!("unsupported content encoding: {}", coding));
+                error.code = 415;
+                return Err(error);
+            }
+        }
+    }
+    Ok(encodings)
+}
+
+/// Undo the content codings as the body is read, refusing more than `limit` bytes of encoded input.
+///
+/// Nothing is decoded up front, the parser reading the returned body bounds the decoded size with
+/// its own limit, so a small body which inflates to gigabytes is cut off early.
+pub(super) async fn decode_body<I>(incoming: I, encodings: &[RequestEncoding], limit: usize) -> Result<DecodedBody> where
+    I: Body + Send + 'static,
+    <I as Body>::Data: Send,
+    Bytes: From<I::Data>,
+    <I as Body>::Error: Into<Box<dyn std::error::Error + Send + Sync>> {
+    let encoded = BodyStream::new(Limited::new(incoming, limit)).filter_map(|frame| async move {
+        match frame {
+            Ok(frame) => frame.into_data().ok().map(|data| Ok(Bytes::from(data))),
+            Err(err) => Some(Err(io::Error::other(err))),
+        }
+    });
+    let mut reader: Pin<Box<dyn AsyncBufRead + Send>> = Box::pin(StreamReader::new(Box::pin(encoded)));
+    for encoding in encodings.iter().rev() {
+        reader = match encoding {
+            RequestEncoding::Gzip => Box::pin(BufReader::new(GzipDecoder::new(reader))),
+            // `deflate` is specified as zlib wrapped, but some clients send raw deflate streams
+            RequestEncoding::Deflate => if is_zlib(reader.fill_buf().await.map_err(|err| read_error(&err))?) {
+                Box::pin(BufReader::new(ZlibDecoder::new(reader)))
+            } else {
+                Box::pin(BufReader::new(DeflateDecoder::new(reader)))
+            },
+            RequestEncoding::Brotli => Box::pin(BufReader::new(BrotliDecoder::new(reader))),
+        };
+    }
+    Ok(StreamBody::new(ReaderStream::new(reader).map_ok(Frame::data).boxed()))
+}
+
+/// A zlib stream starts with a deflate method byte and a header checksum, see RFC 1950.
+fn is_zlib(header: &[u8]) -> bool {
+    match header {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// The error of reading a decoded body, the encoded input can be too large or malformed.
+pub(super) fn read_error(error: &io::Error) -> Error {
+    match error.get_ref().and_then(|inner| inner.downcast_ref::<LengthLimitError>()) {
+        Some(_) => payload_too_large("request body exceeds the size limit"),
+        None => Error::invalid_request_message("cannot decode request body"),
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+use hyper::Method;
+use indexmap::IndexMap;
+use teo_result::{Error, Result};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::connection::connection::Connection;
+use teo_runtime::teon;
+use teo_runtime::value::Value;
+use crate::app::App;
+
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// Answers 200 as long as the server accepts requests
+    pub liveness_path: String,
+    /// Answers 200 once startup finished and every database connection responds, 503 otherwise
+    pub readiness_path: String,
+    /// How long a database ping may take before the connection is reported as failing
+    pub ping_timeout: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            liveness_path: "/healthz".to_owned(),
+            readiness_path: "/readyz".to_owned(),
+            ping_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Answer liveness and readiness probes, the paths are matched without the server path prefix.
+pub(super) async fn health_response(config: &HealthConfig, app: &App, request: &Request) -> Result<Option<Response>> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return Ok(None);
+    }
+    if request.path() == config.liveness_path {
+        Ok(Some(Response::teon(teon!({ "status": "ok" }))))
+    } else if request.path() == config.readiness_path {
+        Ok(Some(readiness_response(config, app).await))
+    } else {
+        Ok(None)
+    }
+}
+
+async fn readiness_response(config: &HealthConfig, app: &App) -> Response {
+    let started = app.is_ready();
+    let mut ready = started;
+    let mut namespaces = IndexMap::new();
+    // connections aren't settled before startup finished, there's nothing to ping yet
+    if started {
+        let conn_ctx = app.conn_ctx();
+        for (namespace_path, connection) in conn_ctx.connections_iter() {
+            let name = if namespace_path.is_empty() { "main".to_owned() } else { namespace_path.join(".") };
+            let mongo = conn_ctx.namespace().namespace_at_path(namespace_path)
+                .and_then(|namespace| namespace.connector())
+                .is_some_and(|connector| connector.provider().is_mongo());
+            let error = match tokio::time::timeout(config.ping_timeout, ping(connection.as_ref(), mongo)).await {
+                Ok(Ok(_)) => None,
+                Ok(Err(err)) => Some(err.message().to_owned()),
+                Err(_) => Some(format!("no response within {}ms", config.ping_timeout.as_millis())),
+            };
+            let status = match error {
+                None => teon!({ "status": "ok" }),
+                Some(message) => {
+                    ready = false;
+                    teon!({ "status": "error", "message": message })
+                }
+            };
+            namespaces.insert(name, status);
+        }
+    }
+    let response = Response::teon(teon!({
+        "ready": ready,
+        "started": started,
+        "namespaces": Value::Dictionary(namespaces),
+    }));
+    if !ready {
+        response.set_code(503);
+    }
+    response
+}
+
+/// Run a round trip to the database, handing out a pooled connection alone doesn't reach the server.
+async fn ping(connection: &dyn Connection, mongo: bool) -> Result<()> {
+    let transaction = connection.no_transaction().await?;
+    // the MongoDB connector takes no raw commands, its sessions are checked out from the server
+    if !mongo {
+        transaction.query_raw(&Value::String("SELECT 1".to_owned())).await?;
+    }
+    Ok(())
+}
+
+/// Requests other than probes are refused until migration, autoseed and the setup callback finished.
+pub(super) fn starting_up() -> Error {
+    let mut error = Error::new("server is starting up");
+    error.code = 503;
+    error
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use serde_json::Value as JsonValue;
+use teo_result::Error;
+use teo_runtime::handler::r#match::HandlerMatch;
+use crate::server::utils::handler_key;
+
+#[derive(Debug, Clone)]
+pub struct BodyLimits {
+    /// Maximum size in bytes of a JSON request body
+    pub json_body_size: usize,
+    /// Maximum nesting depth of a JSON request body
+    pub json_depth: usize,
+    /// Maximum size in bytes of a single uploaded file
+    pub file_size: usize,
+    /// Maximum size in bytes of a whole multipart request body
+    pub multipart_size: usize,
+    /// Maximum number of files in a multipart request body
+    pub file_count: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            json_body_size: 16 * 1024 * 1024,
+            json_depth: 64,
+            file_size: 100 * 1024 * 1024,
+            multipart_size: 200 * 1024 * 1024,
+            file_count: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LimitsConfig {
+    /// Limits applied to every handler
+    pub default: BodyLimits,
+    /// Limits overridden by handler, keyed by dotted handler path like `User.create`
+    pub handlers: BTreeMap<String, BodyLimits>,
+}
+
+impl LimitsConfig {
+    pub fn for_handler(&self, handler_match: &HandlerMatch) -> &BodyLimits {
+        self.handlers.get(&handler_key(handler_match)).unwrap_or(&self.default)
+    }
+}
+
+pub(super) fn payload_too_large(message: impl Into<String>) -> Error {
+    let mut error = Error::new(message);
+    error.code = 413;
+    error
+}
+
+pub(super) fn json_depth(value: &JsonValue) -> usize {
+    match value {
+        JsonValue::Array(array) => 1 + array.iter().map(json_depth).max().unwrap_or(0),
+        JsonValue::Object(object) => 1 + object.values().map(json_depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Whether the nesting depth of a JSON document is over `limit`, checked on the raw bytes so a
+/// deeply nested body is rejected before the parser recurses into it.
+pub(super) fn raw_json_depth_exceeds(body: &[u8], limit: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for byte in body {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if *byte == b'\\' {
+                escaped = true;
+            } else if *byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > limit {
+                    return true;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    false
+}
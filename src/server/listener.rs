@@ -0,0 +1,117 @@
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
+use futures_util::future::select_all;
+use teo_result::{Error, Result};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenerConfig {
+    /// Listen on a TCP address, like `0.0.0.0:5050` or `[::]:5050`
+    Tcp { addr: SocketAddr },
+    /// Listen on a Unix domain socket, a stale socket file at `path` which refuses connections is replaced
+    #[cfg(unix)]
+    Unix {
+        path: PathBuf,
+        /// File permissions of the socket like `0o660`, `None` keeps the umask default
+        mode: Option<u32>,
+    },
+}
+
+impl Display for ListenerConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenerConfig::Tcp { addr } => write!(f, "{}", addr),
+            #[cfg(unix)]
+            ListenerConfig::Unix { path, .. } => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub(super) enum Listener {
+    Tcp(TcpListener),
+    /// The socket file is removed on drop when the path is set, inherited sockets keep theirs
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>),
+}
+
+pub(super) enum Accepted {
+    Tcp(TcpStream, SocketAddr),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Listener {
+
+    pub(super) async fn bind(config: &ListenerConfig) -> Result<Self> {
+        match config {
+            ListenerConfig::Tcp { addr } => match TcpListener::bind(addr).await {
+                Ok(listener) => Ok(Listener::Tcp(listener)),
+                Err(err) => Err(Error::new(format!("cannot listen on {}: {}", addr, err))),
+            },
+            #[cfg(unix)]
+            ListenerConfig::Unix { path, mode } => {
+                use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+                use std::os::unix::net::UnixStream as StdUnixStream;
+                // a previous run which didn't shut down cleanly leaves its socket file behind, it's
+                // stale when nothing accepts connections on it anymore
+                if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                    match StdUnixStream::connect(path) {
+                        Ok(_) => return Err(Error::new(format!("cannot listen on {}: another server is listening", path.display()))),
+                        Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+                        Err(_) => (),
+                    }
+                }
+                // create the socket accessible to the owner only, so nobody connects before the mode
+                // is applied, the umask is process wide but only held for the bind
+                let umask = mode.map(|_| unsafe { libc::umask(0o177) });
+                let bound = UnixListener::bind(path);
+                if let Some(umask) = umask {
+                    unsafe { libc::umask(umask) };
+                }
+                let listener = match bound {
+                    Ok(listener) => listener,
+                    Err(err) => return Err(Error::new(format!("cannot listen on {}: {}", path.display(), err))),
+                };
+                if let Some(mode) = mode {
+                    std::fs::set_permissions(path, std::fs::Permissions::from_mode(*mode))?;
+                }
+                Ok(Listener::Unix(listener, Some(path.clone())))
+            }
+        }
+    }
+
+    pub(super) async fn accept(&self) -> std::io::Result<Accepted> {
+        match self {
+            Listener::Tcp(listener) => listener.accept().await.map(|(stream, peer_addr)| Accepted::Tcp(stream, peer_addr)),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.accept().await.map(|(stream, _)| Accepted::Unix(stream)),
+        }
+    }
+
+    pub(super) fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Listener::Unix(_, _) => None,
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, Some(path)) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Accept from whichever listener has a connection first.
+pub(super) async fn accept_any(listeners: &[Listener]) -> std::io::Result<Accepted> {
+    let (accepted, _, _) = select_all(listeners.iter().map(|listener| Box::pin(listener.accept()))).await;
+    accepted
+}
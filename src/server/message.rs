@@ -4,14 +4,21 @@ use teo_runtime::app::runtime_version::RuntimeVersion;
 use teo_result::Result;
 use crate::prelude::message::info_message;
 
-pub fn server_start_message(port: u16, runtime_version: &RuntimeVersion, entrance: &Entrance, silent: bool) -> Result<()> {
+pub fn server_start_message(addresses: &[String], tls: bool, runtime_version: &RuntimeVersion, entrance: &Entrance, silent: bool) -> Result<()> {
     if silent { return Ok(()) }
     // Introducing
     let teo_version = env!("CARGO_PKG_VERSION");
     let teo = format!("Teo {}", teo_version);
     info_message(format!("{} ({}, {})", teo, runtime_version.to_string(), entrance.to_str()));
     // Listening
-    let port_str = format!("{port}").bold();
-    info_message(format!("listening on port {}", port_str));
+    let addresses_str = addresses.iter().map(|address| address.bold().to_string()).collect::<Vec<_>>().join(", ");
+    let tls_str = if tls { "on" } else { "off" };
+    info_message(format!("listening on {} (TLS {})", addresses_str, tls_str));
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn server_shutdown_message(connections: usize, silent: bool) -> Result<()> {
+    if silent { return Ok(()) }
+    info_message(format!("shutting down, draining {} active connection{}", connections, if connections == 1 { "" } else { "s" }));
+    Ok(())
+}
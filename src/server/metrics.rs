@@ -0,0 +1,166 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, StatusCode};
+use hyper_util::rt::TokioIo;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use teo_result::{Error, Result};
+use teo_runtime::namespace::Namespace;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use crate::server::utils::matched_handler;
+
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    /// Path of the scrape endpoint
+    pub path: String,
+    /// Serve the endpoint on this port of the first TCP listener instead of next to the API
+    pub port: Option<u16>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            path: "/metrics".to_owned(),
+            port: None,
+        }
+    }
+}
+
+const REQUEST_LABELS: [&str; 4] = ["namespace", "model", "action", "status"];
+
+/// The registry of a server, shared by all of its connections.
+#[derive(Debug)]
+pub(super) struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    requests_in_flight: IntGauge,
+    request_body_bytes: IntCounterVec,
+}
+
+/// Counts a request as in flight until dropped.
+pub(super) struct InFlight(IntGauge);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+impl Metrics {
+
+    pub(super) fn new() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("teo_http_requests_total", "Requests handled, by matched handler and status"),
+            &REQUEST_LABELS,
+        ).unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("teo_http_request_duration_seconds", "Time from receiving a request until its response is ready"),
+            &REQUEST_LABELS,
+        ).unwrap();
+        let requests_in_flight = IntGauge::new("teo_http_requests_in_flight", "Requests currently being handled").unwrap();
+        let request_body_bytes = IntCounterVec::new(
+            Opts::new("teo_http_request_body_bytes_total", "Bytes uploaded in request bodies, by matched handler"),
+            &REQUEST_LABELS[..3],
+        ).unwrap();
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        registry.register(Box::new(requests_in_flight.clone())).unwrap();
+        registry.register(Box::new(request_body_bytes.clone())).unwrap();
+        Self { registry, requests, request_duration, requests_in_flight, request_body_bytes }
+    }
+
+    pub(super) fn start(&self) -> InFlight {
+        self.requests_in_flight.inc();
+        InFlight(self.requests_in_flight.clone())
+    }
+
+    pub(super) fn observe<B>(&self, main_namespace: &Namespace, request: &Request, response: &hyper::Response<B>, request_size: Option<u64>, latency: Duration) {
+        let (namespace, model, action) = match matched_handler(main_namespace, request) {
+            Some(handler) => (namespace_label(&handler.namespace), handler.model.unwrap_or_default(), handler.action),
+            None => (String::new(), String::new(), String::new()),
+        };
+        let status = response.status().as_u16().to_string();
+        let labels = [namespace.as_str(), model.as_str(), action.as_str(), status.as_str()];
+        self.requests.with_label_values(&labels).inc();
+        self.request_duration.with_label_values(&labels).observe(latency.as_secs_f64());
+        if let Some(request_size) = request_size {
+            self.request_body_bytes.with_label_values(&labels[..3]).inc_by(request_size);
+        }
+    }
+
+    /// The metrics in Prometheus text format.
+    pub(super) fn render(&self) -> Result<String> {
+        let mut buffer = vec![];
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            return Err(Error::internal_server_error_message(format!("cannot encode metrics: {}", err)));
+        }
+        match String::from_utf8(buffer) {
+            Ok(text) => Ok(text),
+            Err(_) => Err(Error::internal_server_error_message("cannot encode metrics")),
+        }
+    }
+}
+
+fn namespace_label<T: AsRef<str>>(path: &[T]) -> String {
+    if path.is_empty() {
+        "main".to_owned()
+    } else {
+        path.iter().map(|name| name.as_ref()).collect::<Vec<_>>().join(".")
+    }
+}
+
+/// Answer scrapes on the API port, before any middleware runs.
+pub(super) fn metrics_response(config: &MetricsConfig, metrics: &Metrics, request: &Request) -> Result<Option<Response>> {
+    if config.port.is_some() || request.method() != Method::GET || request.path() != config.path {
+        return Ok(None);
+    }
+    Ok(Some(Response::string(metrics.render()?, TextEncoder::new().format_type())?))
+}
+
+/// Serve scrapes on a dedicated listener until the server shuts down.
+pub(super) async fn serve_metrics(listener: TcpListener, path: String, metrics: Arc<Metrics>, mut shutdown: watch::Receiver<bool>) {
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    eprintln!("Error accepting metrics connection: {:?}", err);
+                    continue
+                }
+            },
+            _ = shutdown.wait_for(|shutdown| *shutdown) => break,
+        };
+        let path = path.clone();
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request: hyper::Request<Incoming>| {
+                let response = scrape_response(&path, &metrics, &request);
+                async move { Ok::<_, Infallible>(response) }
+            });
+            if let Err(err) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                eprintln!("Error serving metrics connection: {:?}", err);
+            }
+        });
+    }
+}
+
+fn scrape_response(path: &str, metrics: &Metrics, request: &hyper::Request<Incoming>) -> hyper::Response<Full<Bytes>> {
+    let builder = hyper::Response::builder();
+    if request.method() != Method::GET || request.uri().path() != path {
+        return builder.status(StatusCode::NOT_FOUND).body(Full::default()).unwrap();
+    }
+    match metrics.render() {
+        Ok(text) => builder.header(CONTENT_TYPE, TextEncoder::new().format_type()).body(Full::from(text)).unwrap(),
+        Err(err) => builder.status(StatusCode::INTERNAL_SERVER_ERROR).body(Full::from(err.message().to_owned())).unwrap(),
+    }
+}
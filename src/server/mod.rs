@@ -1,4 +1,28 @@
 pub mod server;
+pub mod config;
+pub mod tls;
+pub mod access_log;
+pub mod request_id;
+pub mod shutdown;
+pub mod limits;
+pub mod listener;
+#[cfg(unix)]
+pub mod systemd;
+pub mod timeout;
+pub mod transaction;
+pub mod compression;
+pub mod cache;
+pub mod binary;
+pub mod cors;
+pub mod rate_limit;
+pub mod health;
+pub mod metrics;
+pub mod trace;
+pub mod openapi;
+pub mod batch;
+pub mod decompression;
+pub mod urlencoded;
+pub mod sse;
 pub mod message;
 pub mod parse_body;
 pub mod utils;
@@ -6,3 +30,5 @@ pub mod handler_found;
 pub mod response;
 pub mod test_request;
 pub mod test_response;
+pub mod websocket;
+pub mod test_websocket;
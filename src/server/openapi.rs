@@ -0,0 +1,28 @@
+use hyper::Method;
+use teo_result::Result;
+use teo_runtime::namespace::Namespace;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use crate::openapi::openapi_document;
+
+#[derive(Debug, Clone)]
+pub struct OpenApiConfig {
+    /// Path serving the document, matched without the server path prefix
+    pub path: String,
+}
+
+impl Default for OpenApiConfig {
+    fn default() -> Self {
+        Self {
+            path: "/openapi.json".to_owned(),
+        }
+    }
+}
+
+/// Serve the document `teo generate openapi` writes, before any middleware runs.
+pub(super) fn openapi_response(config: &OpenApiConfig, main_namespace: &Namespace, request: &Request) -> Result<Option<Response>> {
+    if request.method() != Method::GET || request.path() != config.path {
+        return Ok(None);
+    }
+    Ok(Some(Response::string(openapi_document(main_namespace).to_string(), "application/json")?))
+}
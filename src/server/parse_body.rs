@@ -2,35 +2,164 @@ use std::ffi::OsStr;
 use std::io::Write;
 use std::path::Path;
 use bytes::Bytes;
-use http_body_util::{BodyStream, BodyExt};
+use http_body_util::{BodyStream, BodyExt, Limited};
+use http_body_util::LengthLimitError;
 use futures_util::{StreamExt, TryStreamExt};
 use hyper::body::{Body, Incoming};
-use hyper::header::CONTENT_TYPE;
+use hyper::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use hyper::Method;
 use regex::Regex;
 use serde_json::{json, Value as JsonValue};
 use teo_result::{Result, Error};
+use teo_parser::ast::handler::HandlerInputFormat;
 use teo_runtime::request::Request;
-use multer::Multipart;
+use multer::{Constraints, Multipart, SizeLimit};
+use crate::server::binary::BinaryFormat;
+use crate::server::decompression::{decode_body, read_error, request_encodings};
+use crate::server::limits::{json_depth, payload_too_large, raw_json_depth_exceeds, BodyLimits};
+use crate::server::urlencoded::{parse_query, parse_urlencoded};
 
-pub(super) async fn parse_json_body(incoming: impl Body) -> Result<JsonValue> {
-    let body = match incoming.collect().await {
-        Ok(body) => body,
-        Err(_) => return Err(Error::internal_server_error_message("cannot read HTTP body")),
-    }.to_bytes();
+/// The parser used for a request body, picked from the request `Content-Type` so that a form
+/// handler can accept either of the form encodings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BodyFormat {
+    Json,
+    Multipart,
+    UrlEncoded,
+    Binary(BinaryFormat),
+}
+
+fn body_format(request: &Request, format: HandlerInputFormat) -> Result<BodyFormat> {
+    let essence = request.headers().get(CONTENT_TYPE.as_str())?
+        .map(|content_type| content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase());
+    if let Some(binary_format) = essence.as_deref().and_then(BinaryFormat::from_content_type) {
+        return Ok(BodyFormat::Binary(binary_format));
+    }
+    Ok(match (format, essence.as_deref()) {
+        // a cross site HTML form can post this without a preflight, JSON handlers refuse it
+        (HandlerInputFormat::Json, Some("application/x-www-form-urlencoded")) => {
+            let mut error = Error::new("form encoded bodies are only accepted by form handlers");
+            error.code = 415;
+            return Err(error);
+        }
+        (HandlerInputFormat::Json, _) => BodyFormat::Json,
+        (HandlerInputFormat::Form, Some("application/x-www-form-urlencoded")) => BodyFormat::UrlEncoded,
+        (HandlerInputFormat::Form, _) => BodyFormat::Multipart,
+    })
+}
+
+pub(super) async fn parse_body<I>(request: &Request, format: HandlerInputFormat, incoming: I, limits: &BodyLimits) -> Result<JsonValue> where
+    I: Body + Send + 'static,
+    <I as Body>::Data: Send + 'static,
+    Bytes: From<I::Data>,
+    <I as Body>::Error: std::error::Error + Send + Sync + 'static {
+    if matches!(format, HandlerInputFormat::Json) && (request.method() == Method::GET || request.method() == Method::DELETE) {
+        return parse_query_input(request, limits);
+    }
+    let body_format = body_format(request, format)?;
+    let limit = match body_format {
+        BodyFormat::Json | BodyFormat::UrlEncoded | BodyFormat::Binary(_) => limits.json_body_size,
+        BodyFormat::Multipart => limits.multipart_size,
+    };
+    let encodings = match request.headers().get(CONTENT_ENCODING.as_str())? {
+        Some(content_encoding) => request_encodings(content_encoding.as_ref())?,
+        None => vec![],
+    };
+    if encodings.is_empty() {
+        parse_decoded_body(request, body_format, incoming, limits).await
+    } else {
+        let body = decode_body(incoming, &encodings, limit).await?;
+        parse_decoded_body(request, body_format, body, limits).await
+    }
+}
+
+async fn parse_decoded_body<I>(request: &Request, format: BodyFormat, incoming: I, limits: &BodyLimits) -> Result<JsonValue> where
+    I: Body + Send,
+    <I as Body>::Data: Send + 'static,
+    Bytes: From<I::Data>,
+    <I as Body>::Error: std::error::Error + Send + Sync + 'static {
+    match format {
+        BodyFormat::Json => parse_json_body(incoming, limits).await,
+        BodyFormat::Multipart => parse_form_body(request, incoming, limits).await,
+        BodyFormat::UrlEncoded => parse_urlencoded_body(incoming, limits).await,
+        BodyFormat::Binary(binary_format) => parse_binary_body(binary_format, incoming, limits).await,
+    }
+}
+
+async fn collect_body<B>(incoming: B, limit: usize) -> Result<Bytes> where
+    B: Body,
+    <B as Body>::Error: Into<Box<dyn std::error::Error + Send + Sync>> {
+    match Limited::new(incoming, limit).collect().await {
+        Ok(body) => Ok(body.to_bytes()),
+        Err(err) => if err.downcast_ref::<LengthLimitError>().is_some() {
+            Err(payload_too_large(format!("request body exceeds the limit of {} bytes", limit)))
+        } else if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            // only a body decoded from its content codings fails with an IO error
+            Err(read_error(err))
+        } else {
+            Err(Error::internal_server_error_message("cannot read HTTP body"))
+        },
+    }
+}
+
+pub(super) async fn parse_json_body<B>(incoming: B, limits: &BodyLimits) -> Result<JsonValue> where
+    B: Body,
+    <B as Body>::Error: Into<Box<dyn std::error::Error + Send + Sync>> {
+    let body = collect_body(incoming, limits.json_body_size).await?;
+    if raw_json_depth_exceeds(&body, limits.json_depth) {
+        return Err(json_depth_exceeded(limits));
+    }
     let parsed_json_body_result: std::result::Result<JsonValue, serde_json::Error> = serde_json::from_slice(&body);
     let parsed_json_body = match parsed_json_body_result {
         Ok(b) => b,
+        // serde_json stops at its own nesting limit of 128 whatever the configured depth is
+        Err(err) if err.to_string().starts_with("recursion limit exceeded") => {
+            return Err(json_depth_exceeded(limits));
+        }
         Err(_) => {
             return Err(Error::invalid_request_message("incorrect json format"));
         }
     };
+    check_json_root(parsed_json_body, limits)
+}
+
+/// MessagePack and CBOR bodies are validated like the JSON they decode into.
+async fn parse_binary_body<B>(binary_format: BinaryFormat, incoming: B, limits: &BodyLimits) -> Result<JsonValue> where
+    B: Body,
+    <B as Body>::Error: Into<Box<dyn std::error::Error + Send + Sync>> {
+    let body = collect_body(incoming, limits.json_body_size).await?;
+    check_json_root(binary_format.decode(&body)?, limits)
+}
+
+fn check_json_root(parsed_json_body: JsonValue, limits: &BodyLimits) -> Result<JsonValue> {
     if !parsed_json_body.is_object() {
         return Err(Error::invalid_request_message("expect json root object"));
     }
+    if json_depth(&parsed_json_body) > limits.json_depth {
+        return Err(json_depth_exceeded(limits));
+    }
     Ok(parsed_json_body)
 }
 
-pub(super) async fn parse_form_body<I>(request: &Request, incoming: I) -> Result<JsonValue> where
+fn json_depth_exceeded(limits: &BodyLimits) -> Error {
+    payload_too_large(format!("request body exceeds the nesting depth limit of {}", limits.json_depth))
+}
+
+pub(super) fn parse_query_input(request: &Request, limits: &BodyLimits) -> Result<JsonValue> {
+    let Some(query) = request.query().map(|query| query.to_string()) else {
+        return Ok(JsonValue::Null);
+    };
+    parse_query(&query, limits.json_depth)
+}
+
+pub(super) async fn parse_urlencoded_body<B>(incoming: B, limits: &BodyLimits) -> Result<JsonValue> where
+    B: Body,
+    <B as Body>::Error: Into<Box<dyn std::error::Error + Send + Sync>> {
+    let body = collect_body(incoming, limits.json_body_size).await?;
+    parse_urlencoded(&body, limits.json_depth)
+}
+
+pub(super) async fn parse_form_body<I>(request: &Request, incoming: I, limits: &BodyLimits) -> Result<JsonValue> where
     I: Body + Send,
     <I as Body>::Data: Send + 'static,
     Bytes: From<I::Data>,
@@ -48,11 +177,14 @@ pub(super) async fn parse_form_body<I>(request: &Request, incoming: I) -> Result
         .filter_map(|result| async move { result.map(|frame| frame.into_data().ok()).transpose() });
 
     // Create a Multipart instance from the request body.
-    let mut multipart = Multipart::new(body_stream, boundary);
+    let constraints = Constraints::new().size_limit(SizeLimit::new().whole_stream(limits.multipart_size as u64));
+    let mut multipart = Multipart::with_constraints(body_stream, boundary, constraints);
 
     let mut result_value = json!({});
+    let mut file_count = 0;
+    let mut uploaded_files = UploadedFiles::default();
 
-    while let Some(mut field) = multipart.next_field().await? {
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
 
         // Get the field name.
         let name = field.name();
@@ -68,12 +200,22 @@ pub(super) async fn parse_form_body<I>(request: &Request, incoming: I) -> Result
         let content_type = field.content_type();
 
         if let Some(file_name) = file_name {
+            file_count += 1;
+            if file_count > limits.file_count {
+                return Err(payload_too_large(format!("request contains more than {} files", limits.file_count)));
+            }
             let file_name_ext = Path::new(&file_name)
                 .extension()
                 .and_then(OsStr::to_str);
-            let filepath = std::env::temp_dir().join(file_name.clone()).to_str().unwrap().to_owned();
-            let mut file = std::fs::File::create(&filepath)?;
-            while let Some(field_chunk) = field.chunk().await? {
+            let filepath = upload_path(file_name_ext);
+            let mut file = std::fs::File::create_new(&filepath)?;
+            uploaded_files.paths.push(filepath.clone());
+            let mut file_size = 0;
+            while let Some(field_chunk) = field.chunk().await.map_err(multipart_error)? {
+                file_size += field_chunk.len();
+                if file_size > limits.file_size {
+                    return Err(payload_too_large(format!("file `{}` exceeds the limit of {} bytes", owned_field_name, limits.file_size)));
+                }
                 file.write_all(&field_chunk)?;
             }
             if owned_field_name.ends_with("[]") {
@@ -113,9 +255,51 @@ pub(super) async fn parse_form_body<I>(request: &Request, incoming: I) -> Result
         } else {
             result_value.as_object_mut().unwrap().insert(field.name().unwrap().to_owned(), serde_json::Value::String(match field.text().await {
                 Ok(text) => text,
+                Err(err @ multer::Error::StreamSizeExceeded { .. }) => return Err(multipart_error(err)),
                 Err(_) => return Err(Error::invalid_request_message("cannot read text content")),
             }));
         }
     }
+    uploaded_files.keep();
     Ok(result_value)
 }
+
+/// A fresh path in the temp dir, the client's file name is only kept as metadata since it may
+/// contain path segments or name an existing file.
+fn upload_path(ext: Option<&str>) -> String {
+    let mut name = format!("teo-upload-{}", uuid::Uuid::new_v4());
+    if let Some(ext) = ext.filter(|ext| ext.chars().all(|c| c.is_ascii_alphanumeric())) {
+        name.push('.');
+        name.push_str(ext);
+    }
+    std::env::temp_dir().join(name).to_str().unwrap().to_owned()
+}
+
+/// The files written for a multipart body, removed when the body is rejected part way through
+/// or the request is dropped, so that only a fully parsed body leaves files behind.
+#[derive(Default)]
+struct UploadedFiles {
+    paths: Vec<String>,
+}
+
+impl UploadedFiles {
+    fn keep(&mut self) {
+        self.paths.clear();
+    }
+}
+
+impl Drop for UploadedFiles {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn multipart_error(error: multer::Error) -> Error {
+    match error {
+        multer::Error::StreamSizeExceeded { limit } => payload_too_large(format!("request body exceeds the limit of {} bytes", limit)),
+        multer::Error::StreamReadFailed(err) if err.is::<std::io::Error>() => read_error(err.downcast_ref::<std::io::Error>().unwrap()),
+        error => error.into(),
+    }
+}
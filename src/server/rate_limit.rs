@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use futures_util::future::BoxFuture;
+use hyper::header::{HeaderValue, RETRY_AFTER};
+use teo_result::{Error, Result};
+use teo_runtime::handler::r#match::HandlerMatch;
+use teo_runtime::request::Request;
+use crate::server::utils::handler_key;
+
+const RATE_LIMIT_STATE_KEY: &str = "__teo_rate_limit_state";
+
+/// How requests are grouped into buckets.
+#[derive(Clone)]
+pub enum RateLimitKey {
+    /// The IP address of the connected peer
+    PeerIp,
+    /// The value of a request header like an API key, requests without it are keyed by peer IP
+    Header(String),
+    /// The authenticated identity, extracted from the request after the request middlewares ran,
+    /// anonymous requests are keyed by peer IP
+    Identity(Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>),
+}
+
+impl Debug for RateLimitKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateLimitKey::PeerIp => f.write_str("PeerIp"),
+            RateLimitKey::Header(name) => f.debug_tuple("Header").field(name).finish(),
+            RateLimitKey::Identity(_) => f.write_str("Identity"),
+        }
+    }
+}
+
+/// A token bucket holding `requests` tokens which refills completely over `period`.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period: Duration,
+    pub key: RateLimitKey,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, period: Duration, key: RateLimitKey) -> Self {
+        Self { requests, period, key }
+    }
+
+    fn refill_interval(&self) -> Duration {
+        self.period / self.requests.max(1)
+    }
+}
+
+/// The outcome of taking a token from a bucket.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitState {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again
+    pub reset: Duration,
+    /// Set when the request was rejected, time until a token is available
+    pub retry_after: Option<Duration>,
+}
+
+/// Where bucket state lives, implement this to share limits between server instances.
+pub trait RateLimitStore: Send + Sync {
+    fn acquire<'a>(&'a self, bucket: &'a str, limit: &'a RateLimit) -> BoxFuture<'a, Result<RateLimitState>>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl MemoryRateLimitStore {
+
+    const MAX_BUCKETS: usize = 100_000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn acquire_now(&self, bucket: &str, limit: &RateLimit, now: Instant) -> RateLimitState {
+        let capacity = limit.requests as f64;
+        let interval = limit.refill_interval().as_secs_f64();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= Self::MAX_BUCKETS && !buckets.contains_key(bucket) {
+            // buckets which have refilled completely carry no information
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated_at).as_secs_f64() / interval < capacity);
+        }
+        if buckets.len() >= Self::MAX_BUCKETS && !buckets.contains_key(bucket) {
+            // still full of active buckets, drop the least recently used tenth at once so the next
+            // new clients don't each pay for a scan
+            let mut updated_at = buckets.values().map(|b| b.updated_at).collect::<Vec<_>>();
+            let (_, cutoff, _) = updated_at.select_nth_unstable(Self::MAX_BUCKETS / 10);
+            let cutoff = *cutoff;
+            buckets.retain(|_, b| b.updated_at > cutoff);
+        }
+        let entry = buckets.entry(bucket.to_owned()).or_insert(Bucket { tokens: capacity, updated_at: now });
+        entry.tokens = (entry.tokens + now.duration_since(entry.updated_at).as_secs_f64() / interval).min(capacity);
+        entry.updated_at = now;
+        let retry_after = if entry.tokens >= 1.0 {
+            entry.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - entry.tokens) * interval))
+        };
+        RateLimitState {
+            limit: limit.requests,
+            remaining: entry.tokens.floor() as u32,
+            reset: Duration::from_secs_f64((capacity - entry.tokens) * interval),
+            retry_after,
+        }
+    }
+}
+
+impl RateLimitStore for MemoryRateLimitStore {
+    fn acquire<'a>(&'a self, bucket: &'a str, limit: &'a RateLimit) -> BoxFuture<'a, Result<RateLimitState>> {
+        let state = self.acquire_now(bucket, limit, Instant::now());
+        Box::pin(async move { Ok(state) })
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitConfig {
+    /// Limit for handlers without a more specific limit
+    pub default: Option<RateLimit>,
+    /// Limits by dotted namespace path like `admin.v1`, the longest matching namespace wins
+    pub namespaces: BTreeMap<String, RateLimit>,
+    /// Limits by dotted handler path like `User.signIn`
+    pub handlers: BTreeMap<String, RateLimit>,
+    pub store: Arc<dyn RateLimitStore>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            default: None,
+            namespaces: BTreeMap::new(),
+            handlers: BTreeMap::new(),
+            store: Arc::new(MemoryRateLimitStore::new()),
+        }
+    }
+}
+
+impl Debug for RateLimitConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimitConfig")
+            .field("default", &self.default)
+            .field("namespaces", &self.namespaces)
+            .field("handlers", &self.handlers)
+            .finish()
+    }
+}
+
+impl RateLimitConfig {
+
+    /// The most specific limit for the handler and the scope naming its bucket.
+    fn for_handler(&self, handler_match: &HandlerMatch) -> Option<(String, &RateLimit)> {
+        let key = handler_key(handler_match);
+        if let Some(limit) = self.handlers.get(&key) {
+            return Some((format!("handler:{}", key), limit));
+        }
+        let path = handler_match.path();
+        for length in (1..=path.len()).rev() {
+            let namespace = path[..length].join(".");
+            if let Some(limit) = self.namespaces.get(&namespace) {
+                return Some((format!("namespace:{}", namespace), limit));
+            }
+        }
+        self.default.as_ref().map(|limit| ("global".to_owned(), limit))
+    }
+
+    /// Take a token for the request, the state is kept on the request for the response headers.
+    pub(super) async fn check(&self, request: &Request, handler_match: &HandlerMatch, peer_addr: Option<SocketAddr>) -> Result<()> {
+        let Some((scope, limit)) = self.for_handler(handler_match) else {
+            return Ok(());
+        };
+        let client = match &limit.key {
+            RateLimitKey::PeerIp => None,
+            RateLimitKey::Header(name) => request.headers().get(name.as_str())?.map(|value| format!("key:{}", value)),
+            RateLimitKey::Identity(identity) => identity(request).map(|identity| format!("key:{}", identity)),
+        };
+        // the prefixes keep a key which looks like an IP address out of that address's bucket
+        let client = client
+            .or_else(|| peer_addr.map(|addr| format!("ip:{}", addr.ip())))
+            .unwrap_or_else(|| "-".to_owned());
+        let bucket = format!("{}|{}", scope, client);
+        let state = self.store.acquire(&bucket, limit).await?;
+        request.local_objects().insert(RATE_LIMIT_STATE_KEY, state);
+        match state.retry_after {
+            Some(retry_after) => Err(too_many_requests(retry_after)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Error {
+    let mut error = Error::new(format!("too many requests, retry after {} seconds", ceil_secs(retry_after)));
+    error.code = 429;
+    error
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + if duration.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+/// Add `RateLimit-*` and `Retry-After` headers when the request was rate limited.
+pub(super) fn apply_rate_limit_headers<B>(request: &Request, response: &mut hyper::Response<B>) {
+    let Some(state) = request.local_objects().get::<RateLimitState>(RATE_LIMIT_STATE_KEY).copied() else {
+        return;
+    };
+    let headers = response.headers_mut();
+    headers.insert("ratelimit-limit", HeaderValue::from(state.limit));
+    headers.insert("ratelimit-remaining", HeaderValue::from(state.remaining));
+    headers.insert("ratelimit-reset", HeaderValue::from(ceil_secs(state.reset)));
+    if let Some(retry_after) = state.retry_after {
+        headers.insert(RETRY_AFTER, HeaderValue::from(ceil_secs(retry_after)));
+    }
+}
@@ -0,0 +1,33 @@
+use teo_result::Result;
+use teo_runtime::request::Request;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const REQUEST_ID_KEY: &str = "requestId";
+
+/// The ID assigned to this request, also readable from handlers as the `requestId` local value.
+pub fn request_id(request: &Request) -> Option<String> {
+    request.local_values().get(REQUEST_ID_KEY).ok().flatten()
+}
+
+/// Take the ID from the `X-Request-Id` header when it looks sane, otherwise generate one.
+pub(super) fn assign_request_id(request: &Request) -> Result<String> {
+    if let Some(request_id) = request_id(request) {
+        return Ok(request_id);
+    }
+    let incoming = request.headers().get(REQUEST_ID_HEADER)?.map(|id| id.to_string());
+    let request_id = match incoming {
+        Some(id) if is_valid_request_id(&id) => id,
+        _ => Uuid::new_v4().to_string(),
+    };
+    set_request_id(request, request_id.clone());
+    Ok(request_id)
+}
+
+pub(super) fn set_request_id(request: &Request, request_id: String) {
+    request.local_values().insert(REQUEST_ID_KEY, request_id);
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
@@ -1,7 +1,8 @@
 use bytes::Bytes;
 use http_body_util::{Either, Full};
 use hyper::body::Body;
-use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::header::{HeaderValue, ACCEPT, CONTENT_ENCODING, CONTENT_TYPE, VARY};
+use hyper::StatusCode;
 use mime::APPLICATION_JSON;
 use teo_result::{Error, Result};
 use teo_runtime::request::Request;
@@ -9,33 +10,84 @@ use teo_runtime::response::body::BodyInner;
 use teo_runtime::response::Response;
 use tower_http::services::fs::ServeFileSystemResponseBody;
 use tower_http::services::ServeFile;
+use crate::server::binary::negotiate;
+use crate::server::cache::Validators;
+use crate::server::compression::{compress, ContentEncoding};
+use crate::server::config::ServerConfig;
+use crate::server::shutdown::request_shutdown_handle;
+use crate::server::sse::{EventStream, EventStreamBody, EVENT_STREAM_CONTENT_TYPE, EVENT_STREAM_KEY};
 
-pub async fn hyper_response_from(request: Request, response: Response) -> Result<hyper::Response<Either<Full<Bytes>, ServeFileSystemResponseBody>>> {
+/// Bodies from this size on are compressed on the blocking thread pool.
+const BLOCKING_COMPRESSION_SIZE: usize = 64 * 1024;
+
+pub type HyperResponseBody = Either<Full<Bytes>, Either<ServeFileSystemResponseBody, EventStreamBody>>;
+
+pub async fn hyper_response_from(request: Request, response: Response, config: &ServerConfig) -> Result<hyper::Response<HyperResponseBody>> {
+    let mut content_encoding = None;
+    let mut vary_accept_encoding = false;
+    let mut vary_accept = false;
+    let event_stream = take_event_stream(&request, &response)?;
     let mut hyper_response = {
         match response.body().inner.as_ref() {
-            BodyInner::Empty => {
+            BodyInner::Empty => if let Some(event_stream) = event_stream {
+                let builder = hyper::Response::builder().status(response.code());
+                Ok(builder.body(Either::Right(Either::Right(EventStreamBody::new(event_stream, request_shutdown_handle(&request))))).unwrap())
+            } else {
                 let builder = hyper::Response::builder().status(response.code());
                 let body_bytes = "".to_owned();
                 Ok(builder.body(Either::Left(body_bytes.into())).unwrap())
             },
             BodyInner::String(content) => {
                 let builder = hyper::Response::builder().status(response.code());
-                let body_bytes = content.to_string();
+                let content_type = response.headers().get(CONTENT_TYPE.as_str())?.map(|c| c.to_string());
+                let body_bytes = Bytes::from(content.to_string());
+                vary_accept_encoding = is_compressible(&response, config, content_type.as_deref(), &body_bytes)?;
+                let (body_bytes, encoding) = may_compress(&request, &response, config, content_type.as_deref(), body_bytes).await?;
+                content_encoding = encoding;
                 Ok(builder.body(Either::Left(body_bytes.into())).unwrap())
             },
             BodyInner::Teon(value) => {
-                let mut builder = hyper::Response::builder().status(response.code());
-                builder = builder.header(CONTENT_TYPE, APPLICATION_JSON.to_string());
                 let json_value = serde_json::Value::try_from(value).unwrap();
-                let string_value = serde_json::to_string(&json_value).unwrap();
-                Ok(builder.body(Either::Left(string_value.into())).unwrap())
+                let binary_format = request.headers().get(ACCEPT.as_str())?.and_then(|accept| negotiate(accept.as_ref()));
+                let (content_type, body_value) = match binary_format {
+                    Some(binary_format) => (binary_format.content_type(), binary_format.encode(value)?),
+                    None => (APPLICATION_JSON.as_ref(), Bytes::from(serde_json::to_string(&json_value).unwrap())),
+                };
+                let validators = Validators::new(&config.cache, &request, response.code(), &json_value, &body_value);
+                vary_accept_encoding = is_compressible(&response, config, Some(content_type), &body_value)?;
+                let encoding = response_encoding(&request, &response, config, Some(content_type), &body_value)?;
+                content_encoding = encoding;
+                vary_accept = true;
+                // the tag is known before compressing, a client which has the body gets nothing compressed
+                let not_modified = match validators.as_ref() {
+                    Some(validators) => validators.not_modified(&request, encoding)?,
+                    None => false,
+                };
+                let mut hyper_response = if not_modified {
+                    hyper::Response::builder().status(StatusCode::NOT_MODIFIED).body(Either::Left(Full::default())).unwrap()
+                } else {
+                    let body_bytes = match encoding {
+                        Some(encoding) => compress_body(body_value, encoding).await?,
+                        None => body_value,
+                    };
+                    let builder = hyper::Response::builder().status(response.code()).header(CONTENT_TYPE, content_type);
+                    builder.body(Either::Left(body_bytes.into())).unwrap()
+                };
+                if let Some(validators) = validators {
+                    validators.apply(&mut hyper_response, encoding)?;
+                }
+                Ok(hyper_response)
             },
             BodyInner::File(path_buf) => {
-                let result = ServeFile::new(path_buf).try_call(request.clone_hyper_request_for_file_processing()).await;
+                let mut serve_file = ServeFile::new(path_buf);
+                if config.compression.as_ref().is_some_and(|compression| compression.precompressed) {
+                    serve_file = serve_file.precompressed_br().precompressed_gzip();
+                }
+                let result = serve_file.try_call(request.clone_hyper_request_for_file_processing()).await;
                 match result {
                     Ok(response) => {
                         let (parts, body) = response.into_parts();
-                        Ok(hyper::Response::from_parts(parts, Either::Right(body)))
+                        Ok(hyper::Response::from_parts(parts, Either::Right(Either::Left(body))))
                     }
                     Err(err) => {
                         let error = Error::internal_server_error_message(format!("cannot read file: {:?}", err));
@@ -46,8 +98,85 @@ pub async fn hyper_response_from(request: Request, response: Response) -> Result
         }
     }?;
     response.headers().extend_to(hyper_response.headers_mut());
+    if let Some(content_encoding) = content_encoding {
+        hyper_response.headers_mut().insert(CONTENT_ENCODING, HeaderValue::from_static(content_encoding.as_str()));
+    }
+    // caches must not hand a compressed body to a client which didn't ask for one, or the reverse
+    if vary_accept_encoding {
+        hyper_response.headers_mut().append(VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    // the same data is also served as MessagePack or CBOR
+    if vary_accept {
+        hyper_response.headers_mut().append(VARY, HeaderValue::from_static("accept"));
+    }
     for cookie in response.cookies() {
         hyper_response.headers_mut().append("Set-Cookie", HeaderValue::try_from(cookie.encoded())?);
     }
     Ok(hyper_response)
 }
+
+/// The event stream is kept on the request, it's only sent with the response `into_response`
+/// returned. A middleware which replaced that response would silently drop it otherwise.
+fn take_event_stream(request: &Request, response: &Response) -> Result<Option<EventStream>> {
+    let Some(slot) = request.local_objects().get::<std::sync::Mutex<Option<EventStream>>>(EVENT_STREAM_KEY) else {
+        return Ok(None);
+    };
+    let Some(event_stream) = slot.lock().unwrap().take() else {
+        return Ok(None);
+    };
+    let content_type = response.headers().get(CONTENT_TYPE.as_str())?.map(|content_type| content_type.to_string());
+    if !matches!(response.body().inner.as_ref(), BodyInner::Empty) || content_type.as_deref() != Some(EVENT_STREAM_CONTENT_TYPE) {
+        return Err(Error::internal_server_error_message("the event stream response was replaced by a middleware"));
+    }
+    Ok(Some(event_stream))
+}
+
+/// Whether this body is compressed for clients which accept it, so that the response varies by
+/// `Accept-Encoding` whether or not this client gets it compressed.
+fn is_compressible(response: &Response, config: &ServerConfig, content_type: Option<&str>, body: &Bytes) -> Result<bool> {
+    let Some(compression) = config.compression.as_ref() else {
+        return Ok(false);
+    };
+    if body.len() < compression.min_size || !content_type.is_some_and(|content_type| compression.compresses_content_type(content_type)) {
+        return Ok(false);
+    }
+    // the handler has already encoded this body itself
+    Ok(response.headers().get(CONTENT_ENCODING.as_str())?.is_none())
+}
+
+async fn may_compress(request: &Request, response: &Response, config: &ServerConfig, content_type: Option<&str>, body: Bytes) -> Result<(Bytes, Option<ContentEncoding>)> {
+    match response_encoding(request, response, config, content_type, &body)? {
+        Some(encoding) => Ok((compress_body(body, encoding).await?, Some(encoding))),
+        None => Ok((body, None)),
+    }
+}
+
+/// The encoding this client gets the body in, `None` when it's sent as is.
+fn response_encoding(request: &Request, response: &Response, config: &ServerConfig, content_type: Option<&str>, body: &Bytes) -> Result<Option<ContentEncoding>> {
+    if !is_compressible(response, config, content_type, body)? {
+        return Ok(None);
+    }
+    let Some(compression) = config.compression.as_ref() else {
+        return Ok(None);
+    };
+    let Some(accept_encoding) = request.headers().get("accept-encoding")?.map(|a| a.to_string()) else {
+        return Ok(None);
+    };
+    Ok(compression.negotiate(&accept_encoding))
+}
+
+async fn compress_body(body: Bytes, encoding: ContentEncoding) -> Result<Bytes> {
+    // large bodies would hold up the other tasks of this worker thread for too long
+    let compressed = if body.len() >= BLOCKING_COMPRESSION_SIZE {
+        match tokio::task::spawn_blocking(move || compress(&body, encoding)).await {
+            Ok(compressed) => compressed,
+            Err(err) => return Err(Error::internal_server_error_message(format!("cannot compress response body: {}", err))),
+        }
+    } else {
+        compress(&body, encoding)
+    };
+    match compressed {
+        Ok(compressed) => Ok(compressed),
+        Err(err) => Err(Error::internal_server_error_message(format!("cannot compress response body: {}", err))),
+    }
+}
@@ -1,20 +1,27 @@
 use std::future::Future;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
 use http_body_util::{Either, Full};
 use hyper::body::{Body, Bytes, Incoming};
 use hyper::header::CONTENT_TYPE;
-use hyper::Method;
-use hyper::server::conn::http1;
+use hyper::{Method, StatusCode};
 use hyper::service::Service;
-use hyper_util::rt::TokioIo;
-use teo_parser::ast::handler::HandlerInputFormat;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
 use teo_runtime::{connection};
 use teo_runtime::connection::transaction;
 use teo_runtime::request::Request;
 use teo_runtime::response::Response;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
-use serde_json::{json, Value as JsonValue};
+use tokio::task::JoinSet;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::client_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use serde_json::json;
 use teo_parser::diagnostics::diagnostics::Diagnostics;
 use teo_result::ErrorSerializable;
 use teo_runtime::handler::default::{aggregate, copy, copy_many, count, create, create_many, delete, delete_many, find_first, find_many, find_unique, group_by, update, update_many, upsert};
@@ -22,32 +29,77 @@ use teo_runtime::handler::input::{validate_and_transform_json_input_for_builtin_
 use teo_runtime::middleware::next::Next;
 use teo_runtime::middleware::middleware_imp::MiddlewareImp;
 use teo_runtime::schema::load::load_data_sets::load_data_sets;
-use tower_http::services::fs::ServeFileSystemResponseBody;
 use crate::app::App;
 use crate::cli::command::SeedCommandAction;
 use crate::database::connect_databases;
 use crate::migrate::migrate;
-use crate::server::message::server_start_message;
+use crate::server::access_log::{content_length, AccessLogRequest, AccessLoggedBody, AccessLogger};
+use crate::server::config::{HttpProtocol, ServerConfig};
+use crate::server::batch::{is_batch_request, process_batch};
+use crate::server::cache::mark_builtin_action;
+use crate::server::cors::{allowed_methods, apply_cors_headers, is_preflight, preflight_response};
+use crate::server::health::{health_response, starting_up};
+use crate::server::metrics::{metrics_response, serve_metrics, Metrics};
+use crate::server::openapi::openapi_response;
+use crate::server::trace::{handler_attributes, request_span, traced_next, Tracing};
+use crate::server::listener::{accept_any, Accepted, Listener, ListenerConfig};
+#[cfg(unix)]
+use crate::server::systemd::inherited_listeners;
+use crate::server::message::{server_shutdown_message, server_start_message};
 use crate::prelude::Result;
 use crate::prelude::Error;
 use crate::purge::purge;
 use crate::seeder::seed::seed;
 use crate::server::handler_found::{find_handler, HandlerFound};
-use crate::server::parse_body::{parse_form_body, parse_json_body};
-use crate::server::response::hyper_response_from;
+use crate::server::parse_body::parse_body;
+use crate::server::rate_limit::apply_rate_limit_headers;
+use crate::server::request_id::{assign_request_id, request_id, REQUEST_ID_HEADER};
+use crate::server::response::{hyper_response_from, HyperResponseBody};
 use crate::server::test_request::TestRequest;
+use crate::server::shutdown::{shutdown_signal, store_shutdown_handle, ShutdownHandle};
 use crate::server::test_response::TestResponse;
-use crate::server::utils::remove_path_prefix;
+use crate::server::test_websocket::TestWebSocket;
+use crate::server::timeout::gateway_timeout;
+use crate::server::tls::tls_acceptor;
+use crate::server::utils::{matched_handler, remove_path_prefix};
+use crate::server::websocket::{prepare_upgrade, start_session, store_upgrade};
 
 #[derive(Clone, Debug)]
 pub struct Server {
     pub app: App,
+    shutdown_handle: ShutdownHandle,
+    access_logger: Option<Arc<AccessLogger>>,
+    metrics: Arc<OnceLock<Option<Arc<Metrics>>>>,
+    tracing: Arc<OnceLock<Option<Arc<Tracing>>>>,
+    peer_addr: Option<SocketAddr>,
 }
 
 impl Server {
 
     pub fn new(app: App) -> Self {
-        Self { app: app.clone() }
+        Self { app: app.clone(), shutdown_handle: ShutdownHandle::new(), access_logger: None, metrics: Arc::new(OnceLock::new()), tracing: Arc::new(OnceLock::new()), peer_addr: None }
+    }
+
+    /// The server config of the app, read on every use so `App::replace_server_config` applies
+    /// to a server which was already created.
+    pub fn config(&self) -> Arc<ServerConfig> {
+        self.app.shared_server_config()
+    }
+
+    /// Metrics keep counting across requests, they're set up from the config on first use.
+    fn metrics(&self) -> Option<Arc<Metrics>> {
+        self.metrics.get_or_init(|| self.config().metrics.as_ref().map(|_| Arc::new(Metrics::new()))).clone()
+    }
+
+    /// The tracer provider is set up from the config on first use.
+    fn tracing(&self) -> Option<Arc<Tracing>> {
+        self.tracing.get_or_init(|| self.config().trace.as_ref().and_then(|trace| match Tracing::new(trace) {
+            Ok(tracing) => Some(Arc::new(tracing)),
+            Err(err) => {
+                eprintln!("Error setting up tracing: {}", err.message());
+                None
+            }
+        })).clone()
     }
 
     pub async fn before_serve(&self) -> Result<()> {
@@ -71,6 +123,7 @@ impl Server {
             let transaction_ctx = transaction::Ctx::new(app.conn_ctx().clone());
             setup.call(transaction_ctx).await?;
         }
+        app.set_ready(true);
         Ok(())
     }
 
@@ -87,43 +140,159 @@ impl Server {
     }
 
     pub async fn serve(&self, silent: bool) -> Result<()> {
-        let bind = &self.app.compiled_main_namespace().server().unwrap().bind;
-        let addr: SocketAddr = match format!("{}:{}", bind.0, bind.1).parse() {
-            Ok(addr) => addr,
-            Err(_) => return Err(Error::new(format!("cannot parse server bind address: {}:{}", bind.0, bind.1))),
+        let config = self.config();
+        let (listeners, addresses): (Vec<Listener>, Vec<String>) = self.listeners().await?.into_iter().unzip();
+        let tls_acceptor = match &config.tls {
+            Some(tls) => Some(tls_acceptor(tls, config.protocol)?),
+            None => None,
+        };
+        let access_logger = match &config.access_log {
+            Some(access_log) if !silent => Some(Arc::new(AccessLogger::new(access_log)?)),
+            _ => None,
         };
-        let listener = TcpListener::bind(addr).await?;
-        server_start_message(bind.1, &self.app.runtime_version(), &self.app.entrance(), silent)?;
-        // We start a loop to continuously accept incoming connections
+        if let (Some(metrics), Some(metrics_config)) = (self.metrics(), &config.metrics) {
+            if let Some(port) = metrics_config.port {
+                // without a TCP listener there's no public address to share, keep scrapes local
+                let ip = listeners.iter().find_map(|listener| listener.local_addr()).map_or(IpAddr::V4(Ipv4Addr::LOCALHOST), |addr| addr.ip());
+                let metrics_listener = TcpListener::bind(SocketAddr::new(ip, port)).await?;
+                tokio::spawn(serve_metrics(metrics_listener, metrics_config.path.clone(), metrics, self.shutdown_handle.subscribe()));
+            }
+        }
+        server_start_message(&addresses, tls_acceptor.is_some(), &self.app.runtime_version(), &self.app.entrance(), silent)?;
+        let mut connections = JoinSet::new();
+        let signal = shutdown_signal(self.shutdown_handle.subscribe());
+        tokio::pin!(signal);
+        // We start a loop to continuously accept incoming connections until shutdown is requested
         loop {
-            let (stream, _) = listener.accept().await?;
-
-            // Use an adapter to access something implementing `tokio::io` traits as if they implement
-            // `hyper::rt` IO traits.
-            let io = TokioIo::new(stream);
+            let accepted = tokio::select! {
+                accepted = accept_any(&listeners) => accepted?,
+                _ = &mut signal => break,
+            };
+            // Forget about connections which are already closed
+            while connections.try_join_next().is_some() { }
 
             // Spawn a tokio task to serve multiple connections concurrently
-            {
-                let server = self.clone();
-                tokio::task::spawn(async move {
-                    // Finally, we bind the incoming connection to our `hello` service
-                    if let Err(err) = http1::Builder::new()
-                        // `service_fn` converts our function in a `Service`
-                        .serve_connection(io, server)
-                        .await
-                    {
-                        eprintln!("Error serving connection: {:?}", err);
-                    }
-                });
+            let mut server = self.clone();
+            server.access_logger = access_logger.clone();
+            match accepted {
+                Accepted::Tcp(stream, peer_addr) => {
+                    server.peer_addr = Some(peer_addr);
+                    connections.spawn(server.accept_connection(stream, tls_acceptor.clone()));
+                }
+                #[cfg(unix)]
+                Accepted::Unix(stream) => {
+                    connections.spawn(server.accept_connection(stream, tls_acceptor.clone()));
+                }
+            }
+        }
+        // Stop accepting and let active connections finish
+        drop(listeners);
+        self.shutdown_handle.shutdown();
+        server_shutdown_message(connections.len(), silent)?;
+        let drain = async {
+            while connections.join_next().await.is_some() { }
+            // WebSocket sessions and event streams see the shutdown and close on their own
+            self.shutdown_handle.wait_for_tasks().await;
+        };
+        if tokio::time::timeout(config.shutdown.drain_timeout, drain).await.is_err() {
+            connections.abort_all();
+        }
+        // one failing hook doesn't keep the others from releasing their resources
+        for hook in self.app.get_shutdown_hooks() {
+            let transaction_ctx = transaction::Ctx::new(self.app.conn_ctx().clone());
+            if let Err(err) = hook.call(transaction_ctx).await {
+                eprintln!("Error running shutdown hook: {}", err.message());
+            }
+        }
+        if let Some(tracing) = self.tracing() {
+            tracing.shutdown();
+        }
+        Ok(())
+    }
+
+    /// The sockets passed by systemd, the listeners of the server config, or the `bind` of the
+    /// schema, whichever comes first, with a description of each.
+    async fn listeners(&self) -> Result<Vec<(Listener, String)>> {
+        let config = self.config();
+        #[cfg(unix)]
+        if config.socket_activation {
+            let inherited = inherited_listeners()?;
+            if !inherited.is_empty() {
+                return Ok(inherited);
+            }
+        }
+        let listener_configs = if !config.listeners.is_empty() {
+            config.listeners.clone()
+        } else {
+            let bind = &self.app.compiled_main_namespace().server().unwrap().bind;
+            match format!("{}:{}", bind.0, bind.1).parse() {
+                Ok(addr) => vec![ListenerConfig::Tcp { addr }],
+                Err(_) => return Err(Error::new(format!("cannot parse server bind address: {}:{}", bind.0, bind.1))),
+            }
+        };
+        let mut listeners = vec![];
+        for listener_config in listener_configs {
+            listeners.push((Listener::bind(&listener_config).await?, listener_config.to_string()));
+        }
+        Ok(listeners)
+    }
+
+    async fn accept_connection<S>(self, stream: S, tls_acceptor: Option<TlsAcceptor>) where S: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+        // Use an adapter to access something implementing `tokio::io` traits as if they implement
+        // `hyper::rt` IO traits.
+        if let Some(tls_acceptor) = tls_acceptor {
+            match tls_acceptor.accept(stream).await {
+                Ok(stream) => self.serve_connection(TokioIo::new(stream)).await,
+                Err(err) => eprintln!("Error accepting TLS connection: {:?}", err),
+            }
+        } else {
+            self.serve_connection(TokioIo::new(stream)).await
+        }
+    }
+
+    async fn serve_connection<I>(self, io: I) where I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static {
+        let mut shutdown = self.shutdown_handle.subscribe();
+        // HTTP/1.1 and HTTP/2 are negotiated per connection unless restricted by config
+        let builder = Self::connection_builder(self.config().protocol);
+        let connection = builder.serve_connection_with_upgrades(io, self);
+        tokio::pin!(connection);
+        let mut draining = false;
+        let result = loop {
+            tokio::select! {
+                result = connection.as_mut() => break result,
+                _ = shutdown.wait_for(|shutdown| *shutdown), if !draining => {
+                    // Finish in-flight requests, then close the connection
+                    connection.as_mut().graceful_shutdown();
+                    draining = true;
+                }
             }
+        };
+        if let Err(err) = result {
+            eprintln!("Error serving connection: {:?}", err);
+        }
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown_handle.clone()
+    }
+
+    fn connection_builder(protocol: HttpProtocol) -> auto::Builder<TokioExecutor> {
+        let builder = auto::Builder::new(TokioExecutor::new());
+        match protocol {
+            HttpProtocol::Http1 => builder.http1_only(),
+            HttpProtocol::Http2 => builder.http2_only(),
+            HttpProtocol::Auto => builder,
         }
     }
 
-    fn error_to_hyper_response(&self, error: Error) -> hyper::Response<Either<Full<Bytes>, ServeFileSystemResponseBody>> {
+    fn error_to_hyper_response(&self, error: Error, request_id: Option<String>) -> hyper::Response<HyperResponseBody> {
         let mut result_value = json!({
                     "type": error.inferred_title(),
                     "message": error.message(),
                 });
+        if let Some(request_id) = request_id.as_ref() {
+            result_value["requestId"] = json!(request_id);
+        }
         if error.errors.is_some() {
             result_value["errors"] = ErrorSerializable::from_error(&error).errors;
         }
@@ -131,20 +300,64 @@ impl Server {
             "error": result_value
         });
         let error_string = serde_json::to_string(&wrapped).unwrap();
-        hyper::Response::builder().status(error.code).header(CONTENT_TYPE, "application/json").body(Either::Left(error_string.into())).unwrap()
+        let mut builder = hyper::Response::builder().status(error.code).header(CONTENT_TYPE, "application/json");
+        if let Some(request_id) = request_id {
+            builder = builder.header(REQUEST_ID_HEADER, request_id);
+        }
+        builder.body(Either::Left(error_string.into())).unwrap()
     }
 
-    async fn hyper_handler_with_error_responses(self, hyper_request: hyper::Request<Incoming>) -> Result<hyper::Response<Either<Full<Bytes>, ServeFileSystemResponseBody>>> {
+    async fn hyper_handler_with_error_responses(self, hyper_request: hyper::Request<Incoming>) -> Result<hyper::Response<AccessLoggedBody>> {
         match self.hyper_handler(hyper_request).await {
             Ok(response) => Ok(response),
-            Err(error) => Ok(self.error_to_hyper_response(error)),
+            Err(error) => Ok(self.error_to_hyper_response(error, None).map(|body| AccessLoggedBody::new(body, None))),
         }
     }
 
     pub async fn process_request(&self, request: Request) -> Result<Response> {
+        let config = self.config();
+        let request_id = assign_request_id(&request)?;
+        if let Some(health) = config.health.as_ref() {
+            if let Some(response) = health_response(health, &self.app, &request).await? {
+                response.headers().insert(REQUEST_ID_HEADER, request_id)?;
+                return Ok(response);
+            }
+        }
+        if let (Some(metrics), Some(metrics_config)) = (self.metrics(), &config.metrics) {
+            if let Some(response) = metrics_response(metrics_config, &metrics, &request)? {
+                response.headers().insert(REQUEST_ID_HEADER, request_id)?;
+                return Ok(response);
+            }
+        }
+        if !self.app.is_ready() {
+            return Err(starting_up());
+        }
+        if let Some(openapi) = config.openapi.as_ref() {
+            if let Some(response) = openapi_response(openapi, self.app.compiled_main_namespace(), &request)? {
+                response.headers().insert(REQUEST_ID_HEADER, request_id)?;
+                return Ok(response);
+            }
+        }
+        if let Some(response) = self.cors_preflight(&request)? {
+            response.headers().insert(REQUEST_ID_HEADER, request_id)?;
+            return Ok(response);
+        }
+        if let Some(batch) = config.batch.as_ref() {
+            let path_prefix = self.app.compiled_main_namespace().server().unwrap().path_prefix.clone();
+            if is_batch_request(batch, &request, path_prefix.as_ref()) {
+                let response = process_batch(self, batch, &request).await?;
+                response.headers().insert(REQUEST_ID_HEADER, request_id)?;
+                return Ok(response);
+            }
+        }
         let main_namespace = self.app.compiled_main_namespace().clone();
+        let peer_addr = self.peer_addr;
+        let request_middleware_span = request_span(&request).map(|span| span.child("request middleware", vec![]));
+        let parent_span = request_middleware_span.clone();
         let droppable_next = Next::new(move |request: Request| {
             let main_namespace = main_namespace.clone();
+            let config = config.clone();
+            let parent_span = parent_span.clone();
             async move {
                 let path_prefix = main_namespace.server().unwrap().path_prefix.clone();
                 let path = remove_path_prefix(request.path(), path_prefix.as_ref());
@@ -155,75 +368,157 @@ impl Server {
                 let Some((dest_namespace, handler_found)) = find_handler(&main_namespace, &handler_match) else {
                     return Err(Error::not_found());
                 };
+                if let HandlerFound::Builtin(_, _) = handler_found {
+                    mark_builtin_action(&request);
+                }
+                let attributes = matched_handler(&main_namespace, &request).map_or(vec![], |handler| handler_attributes(&handler));
                 if request.method() == Method::OPTIONS {
                     return dest_namespace.handler_middleware_stack().call(request, Next::new(|_: Request| async {
                         Ok::<Response, Error>(Response::empty())
                     })).await;
                 }
-                let incoming_full_bytes = request.take_incoming_bytes_for_test();
-                let incoming = request.take_incoming();
-                if incoming_full_bytes.is_none() && incoming.is_none() {
-                    return Err(Error::internal_server_error_message("HTTP body is taken"))
+                if let Some(rate_limit) = config.rate_limit.as_ref() {
+                    rate_limit.check(&request, &handler_match, peer_addr).await?;
                 }
+                let limits = config.limits.for_handler(&handler_match);
+                let timeout = config.timeouts.for_handler(&handler_match);
+                let transaction_ctx = request.transaction_ctx();
+                let dispatch = async {
+                    let incoming_full_bytes = request.take_incoming_bytes_for_test();
+                    let incoming = request.take_incoming();
+                    if incoming_full_bytes.is_none() && incoming.is_none() {
+                        return Err(Error::internal_server_error_message("HTTP body is taken"))
+                    }
 
-                let body_value = if let Some(incoming) = incoming {
-                    match handler_found.handler_format() {
-                        HandlerInputFormat::Json => if request.method() == Method::GET || request.method() == Method::DELETE {
-                            JsonValue::Null
-                        } else {
-                            parse_json_body(incoming).await?
-                        },
-                        HandlerInputFormat::Form => parse_form_body(&request, incoming).await?,
+                    let body_value = if let Some(incoming) = incoming {
+                        parse_body(&request, handler_found.handler_format(), incoming, limits).await?
+                    } else if let Some(incoming_full_bytes) = incoming_full_bytes {
+                        parse_body(&request, handler_found.handler_format(), incoming_full_bytes, limits).await?
+                    } else {
+                        unreachable!()
+                    };
+                    // dispatch and run
+                    let validation_span = parent_span.as_ref().map(|span| span.child("validate input", attributes.clone()));
+                    let body = match &handler_found {
+                        HandlerFound::Builtin(model, action) => validate_and_transform_json_input_for_builtin_action(model, *action, &body_value, &main_namespace),
+                        HandlerFound::Custom(handler) => validate_and_transform_json_input_for_handler(handler, &body_value, &main_namespace),
+                    };
+                    if let Some(span) = validation_span {
+                        span.end(&body);
                     }
-                } else if let Some(incoming_full_bytes) = incoming_full_bytes {
-                    match handler_found.handler_format() {
-                        HandlerInputFormat::Json => if request.method() == Method::GET || request.method() == Method::DELETE {
-                            JsonValue::Null
-                        } else {
-                            parse_json_body(incoming_full_bytes).await?
+                    request.set_body_value(body?);
+                    let action = match handler_found {
+                        HandlerFound::Builtin(_, _) => match handler_match.handler_name() {
+                            "findMany" => Next::new(find_many),
+                            "findFirst" => Next::new(find_first),
+                            "findUnique" => Next::new(find_unique),
+                            "create" => Next::new(create),
+                            "delete" => Next::new(delete),
+                            "update" => Next::new(update),
+                            "upsert" => Next::new(upsert),
+                            "copy" => Next::new(copy),
+                            "createMany" => Next::new(create_many),
+                            "updateMany" => Next::new(update_many),
+                            "copyMany" => Next::new(copy_many),
+                            "deleteMany" => Next::new(delete_many),
+                            "count" => Next::new(count),
+                            "aggregate" => Next::new(aggregate),
+                            "groupBy" => Next::new(group_by),
+                            _ => Err(Error::not_found())?,
                         },
-                        HandlerInputFormat::Form => parse_form_body(&request, incoming_full_bytes).await?,
+                        HandlerFound::Custom(handler) => handler.call(),
+                    };
+                    let handler_middleware_span = parent_span.as_ref().map(|span| span.child("handler middleware", attributes.clone()));
+                    let action = traced_next(action, handler_middleware_span.as_ref(), "action", attributes.clone());
+                    let result = dest_namespace.handler_middleware_stack().call(request, action).await;
+                    if let Some(span) = handler_middleware_span {
+                        span.end(&result);
                     }
-                } else {
-                    unreachable!()
+                    result
                 };
-                // dispatch and run
-                return match handler_found {
-                    HandlerFound::Builtin(model, action) => {
-                        let body = validate_and_transform_json_input_for_builtin_action(model, action, &body_value, &main_namespace)?;
-                        request.set_body_value(body);
-                        match handler_match.handler_name() {
-                            "findMany" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(find_many)).await?),
-                            "findFirst" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(find_first)).await?),
-                            "findUnique" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(find_unique)).await?),
-                            "create" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(create)).await?),
-                            "delete" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(delete)).await?),
-                            "update" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(update)).await?),
-                            "upsert" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(upsert)).await?),
-                            "copy" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(copy)).await?),
-                            "createMany" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(create_many)).await?),
-                            "updateMany" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(update_many)).await?),
-                            "copyMany" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(copy_many)).await?),
-                            "deleteMany" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(delete_many)).await?),
-                            "count" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(count)).await?),
-                            "aggregate" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(aggregate)).await?),
-                            "groupBy" => Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, Next::new(group_by)).await?),
-                            _ => Err(Error::not_found())?,
+                match timeout {
+                    Some(timeout) => match tokio::time::timeout(timeout, dispatch).await {
+                        Ok(result) => result,
+                        Err(_) => {
+                            // The handler future is dropped at this point, don't leave its transaction open
+                            if let Err(err) = transaction_ctx.abort().await {
+                                eprintln!("Error rolling back timed out request: {:?}", err);
+                            }
+                            Err(gateway_timeout(timeout))
                         }
                     },
-                    HandlerFound::Custom(handler) => {
-                        let body = validate_and_transform_json_input_for_handler(handler, &body_value, &main_namespace)?;
-                        request.set_body_value(body);
-                        Ok::<Response, Error>(dest_namespace.handler_middleware_stack().call(request, handler.call()).await?)
-                    }
+                    None => dispatch.await,
                 }
             }
         });
         let main_namespace = self.app.compiled_main_namespace();
-        let response = main_namespace.request_middleware_stack().call(request.clone(), droppable_next).await?;
+        let response = main_namespace.request_middleware_stack().call(request.clone(), droppable_next).await;
+        if let Some(span) = request_middleware_span {
+            span.end(&response);
+        }
+        let response = response?;
+        response.headers().insert(REQUEST_ID_HEADER, request_id)?;
         Ok(response)
     }
 
+    /// Create the request with `create` and process it. A request routed to a transactional
+    /// handler is created with the ctx of its transaction, so the request middlewares, the handler
+    /// middlewares and the action all see the same request, which commits when it succeeds.
+    async fn run_request<F>(&self, transactional: bool, create: F) -> Result<(Request, Result<Response>)> where F: FnOnce(transaction::Ctx) -> Result<Request> + Send + 'static {
+        let conn_ctx = connection::Ctx::from_namespace(self.app.compiled_main_namespace());
+        let transaction_ctx = transaction::Ctx::new(conn_ctx);
+        if !transactional {
+            let request = create(transaction_ctx)?;
+            let result = self.process_request(request.clone()).await;
+            return Ok((request, result));
+        }
+        let create = Arc::new(Mutex::new(Some(create)));
+        let created = Arc::new(Mutex::new(None));
+        let server = self.clone();
+        let result = transaction_ctx.run_transaction({
+            let created = created.clone();
+            move |transaction_ctx: transaction::Ctx| {
+                let create = create.lock().unwrap().take();
+                let created = created.clone();
+                let server = server.clone();
+                async move {
+                    let Some(create) = create else {
+                        return Err(Error::internal_server_error_message("request is already created"));
+                    };
+                    let request = create(transaction_ctx)?;
+                    *created.lock().unwrap() = Some(request.clone());
+                    let result: Result<Response> = server.process_request(request).await;
+                    result
+                }
+            }
+        }).await;
+        let request = created.lock().unwrap().take();
+        match (request, result) {
+            (Some(request), result) => Ok((request, result)),
+            (None, Err(error)) => Err(error),
+            (None, Ok(_)) => Err(Error::internal_server_error_message("request is not created")),
+        }
+    }
+
+    /// Answer CORS preflights before any middleware runs, with the methods registered for the path.
+    fn cors_preflight(&self, request: &Request) -> Result<Option<Response>> {
+        let config = self.config();
+        let Some(cors) = config.cors.as_ref() else {
+            return Ok(None);
+        };
+        if !is_preflight(request)? {
+            return Ok(None);
+        }
+        let main_namespace = self.app.compiled_main_namespace();
+        let path_prefix = main_namespace.server().unwrap().path_prefix.clone();
+        let path = remove_path_prefix(request.path(), path_prefix.as_ref());
+        let methods = allowed_methods(cors, main_namespace, path);
+        if methods.is_empty() {
+            return Err(Error::not_found());
+        }
+        preflight_response(cors, request, &methods).map(Some)
+    }
+
     pub async fn process_test_request(&self, test_request: TestRequest) -> Result<TestResponse> {
         self.process_test_request_with_hyper_request(test_request.to_hyper_request()?).await
     }
@@ -232,34 +527,125 @@ impl Server {
         match self.process_test_request_inner(test_hyper_request).await {
             Ok(res) => Ok(res),
             Err(err) => {
-                let hyper_res = self.error_to_hyper_response(err);
+                let hyper_res = self.error_to_hyper_response(err, None);
                 TestResponse::new(hyper_res).await
             },
         }
     }
 
+    /// Open a WebSocket against this server in process, the handshake goes through the regular
+    /// HTTP stack over an in-memory stream.
+    pub async fn process_test_websocket(&self, test_request: TestRequest) -> Result<TestWebSocket> {
+        let (parts, _) = test_request.to_hyper_request()?.into_parts();
+        let mut client_request = match format!("ws://localhost{}", parts.uri).into_client_request() {
+            Ok(client_request) => client_request,
+            Err(err) => return Err(Error::new(format!("invalid websocket request: {}", err))),
+        };
+        client_request.headers_mut().extend(parts.headers);
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(self.clone().serve_connection(TokioIo::new(server_io)));
+        match client_async(client_request, client_io).await {
+            Ok((stream, _)) => Ok(TestWebSocket::new(stream)),
+            Err(WsError::Http(response)) => {
+                let mut error = Error::new(format!("websocket handshake rejected with status {}", response.status()));
+                error.code = response.status().as_u16();
+                Err(error)
+            }
+            Err(err) => Err(Error::new(format!("websocket handshake failed: {}", err))),
+        }
+    }
+
     async fn process_test_request_inner(&self, hyper_request: hyper::Request<Full<Bytes>>) -> Result<TestResponse> {
+        let config = self.config();
+        let started_at = Instant::now();
+        let metrics = self.metrics();
+        let in_flight = metrics.as_ref().map(|metrics| metrics.start());
         let main_namespace = self.app.compiled_main_namespace();
-        let conn_ctx = connection::Ctx::from_namespace(main_namespace);
-        let transaction_ctx = transaction::Ctx::new(conn_ctx);
-        let request = Request::new_for_test(hyper_request, transaction_ctx);
-        let response = self.process_request(request.clone()).await?;
-        let hyper_response = hyper_response_from(request, response).await?;
+        let request_size = content_length(hyper_request.headers()).or(hyper_request.body().size_hint().exact());
+        let transactional = config.transaction.is_transactional_route(main_namespace, hyper_request.method(), hyper_request.uri().path());
+        let shutdown_handle = self.shutdown_handle.clone();
+        let tracing = self.tracing();
+        let (request, result) = self.run_request(transactional, move |transaction_ctx| {
+            let request = Request::new_for_test(hyper_request, transaction_ctx);
+            store_shutdown_handle(&request, shutdown_handle);
+            if let Some(tracing) = tracing {
+                tracing.start(&request)?;
+            }
+            Ok(request)
+        }).await?;
+        let trace_span = request_span(&request);
+        let mut hyper_response = match result {
+            Ok(response) => hyper_response_from(request.clone(), response, &config).await,
+            Err(error) => Err(error),
+        }.unwrap_or_else(|error| self.error_to_hyper_response(error, request_id(&request)));
+        if let Some(cors) = config.cors.as_ref() {
+            apply_cors_headers(cors, &request, &mut hyper_response)?;
+        }
+        apply_rate_limit_headers(&request, &mut hyper_response);
+        if let Some(trace_span) = trace_span {
+            let response_header = config.trace.as_ref().is_some_and(|trace| trace.response_header);
+            trace_span.finish(matched_handler(main_namespace, &request), response_header, &mut hyper_response);
+        }
+        if let Some(metrics) = metrics {
+            metrics.observe(main_namespace, &request, &hyper_response, request_size, started_at.elapsed());
+        }
+        drop(in_flight);
         TestResponse::new(hyper_response).await
     }
 
-    async fn hyper_handler(&self, hyper_request: hyper::Request<Incoming>) -> Result<hyper::Response<Either<Full<Bytes>, ServeFileSystemResponseBody>>> {
+    async fn hyper_handler(&self, mut hyper_request: hyper::Request<Incoming>) -> Result<hyper::Response<AccessLoggedBody>> {
+        let config = self.config();
+        let started_at = Instant::now();
+        let metrics = self.metrics();
+        let in_flight = metrics.as_ref().map(|metrics| metrics.start());
         let main_namespace = self.app.compiled_main_namespace();
-        let conn_ctx = connection::Ctx::from_namespace(main_namespace);
-        let transaction_ctx = transaction::Ctx::new(conn_ctx);
-        let request = Request::new(hyper_request, transaction_ctx);
-        let response = self.process_request(request.clone()).await?;
-        hyper_response_from(request, response).await
+        let on_upgrade = prepare_upgrade(&mut hyper_request);
+        let request_size = content_length(hyper_request.headers()).or(hyper_request.body().size_hint().exact());
+        let access_log_request = self.access_logger.as_ref().map(|_| AccessLogRequest::new(&hyper_request, self.peer_addr));
+        let transactional = config.transaction.is_transactional_route(main_namespace, hyper_request.method(), hyper_request.uri().path());
+        let shutdown_handle = self.shutdown_handle.clone();
+        let tracing = self.tracing();
+        let (request, result) = self.run_request(transactional, move |transaction_ctx| {
+            let request = Request::new(hyper_request, transaction_ctx);
+            store_shutdown_handle(&request, shutdown_handle);
+            if let Some(tracing) = tracing {
+                tracing.start(&request)?;
+            }
+            if let Some(on_upgrade) = on_upgrade {
+                store_upgrade(&request, on_upgrade);
+            }
+            Ok(request)
+        }).await?;
+        let trace_span = request_span(&request);
+        let mut hyper_response = match result {
+            Ok(response) => hyper_response_from(request.clone(), response, &config).await,
+            Err(error) => Err(error),
+        }.unwrap_or_else(|error| self.error_to_hyper_response(error, request_id(&request)));
+        if let Some(cors) = config.cors.as_ref() {
+            apply_cors_headers(cors, &request, &mut hyper_response)?;
+        }
+        apply_rate_limit_headers(&request, &mut hyper_response);
+        if hyper_response.status() == StatusCode::SWITCHING_PROTOCOLS {
+            start_session(&request);
+        }
+        if let Some(trace_span) = trace_span {
+            let response_header = config.trace.as_ref().is_some_and(|trace| trace.response_header);
+            trace_span.finish(matched_handler(main_namespace, &request), response_header, &mut hyper_response);
+        }
+        if let Some(metrics) = metrics {
+            metrics.observe(main_namespace, &request, &hyper_response, request_size, started_at.elapsed());
+        }
+        drop(in_flight);
+        let pending_access_log = match (&self.access_logger, access_log_request) {
+            (Some(access_logger), Some(access_log_request)) => Some(access_logger.pending(main_namespace, access_log_request, &request, &hyper_response, started_at)),
+            _ => None,
+        };
+        Ok(hyper_response.map(|body| AccessLoggedBody::new(body, pending_access_log)))
     }
 }
 
 impl Service<hyper::Request<Incoming>> for Server {
-    type Response = hyper::Response<Either<Full<Bytes>, ServeFileSystemResponseBody>>;
+    type Response = hyper::Response<AccessLoggedBody>;
     type Error = Error;
     type Future = Pin<Box<dyn Future<Output = core::result::Result<Self::Response, Self::Error>> + Send>>;
 
@@ -0,0 +1,98 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use teo_runtime::request::Request;
+use tokio::sync::watch;
+use tokio_util::task::TaskTracker;
+
+const SHUTDOWN_HANDLE_KEY: &str = "__teo_shutdown_handle";
+
+#[derive(Debug, Clone)]
+pub struct ShutdownConfig {
+    /// How long active connections may take to finish after the server stops accepting
+    pub drain_timeout: Duration,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A handle to stop a running server programmatically.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+    /// Tasks which outlive their response, like WebSocket sessions, drained with the connections
+    tasks: TaskTracker,
+}
+
+impl ShutdownHandle {
+
+    pub(super) fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self { sender: Arc::new(sender), tasks: TaskTracker::new() }
+    }
+
+    /// Stop accepting connections and drain the active ones.
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    pub(super) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.sender.subscribe()
+    }
+
+    pub(super) fn spawn<F>(&self, task: F) where F: Future<Output = ()> + Send + 'static {
+        self.tasks.spawn(task);
+    }
+
+    /// Wait for the spawned tasks, none can be spawned afterwards.
+    pub(super) async fn wait_for_tasks(&self) {
+        self.tasks.close();
+        self.tasks.wait().await
+    }
+}
+
+/// Let code running for a request, like a WebSocket upgrade or an event stream, follow shutdown.
+pub(super) fn store_shutdown_handle(request: &Request, shutdown_handle: ShutdownHandle) {
+    request.local_objects().insert(SHUTDOWN_HANDLE_KEY, shutdown_handle);
+}
+
+pub(super) fn request_shutdown_handle(request: &Request) -> Option<ShutdownHandle> {
+    request.local_objects().get::<ShutdownHandle>(SHUTDOWN_HANDLE_KEY).cloned()
+}
+
+/// Resolves when the shutdown handle is triggered or the process receives SIGINT or SIGTERM.
+pub(super) async fn shutdown_signal(mut receiver: watch::Receiver<bool>) {
+    let handle = async move {
+        if receiver.wait_for(|shutdown| *shutdown).await.is_err() {
+            std::future::pending::<()>().await
+        }
+    };
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => { signal.recv().await; },
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = handle => (),
+        _ = ctrl_c => (),
+        _ = terminate => (),
+    }
+}
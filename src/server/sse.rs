@@ -0,0 +1,200 @@
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use hyper::body::{Body, Frame};
+use serde_json::Value as JsonValue;
+use teo_result::{Error, Result};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::value::Value;
+use tokio::sync::mpsc;
+use tokio::time::{interval_at, Instant, Interval};
+use crate::server::shutdown::ShutdownHandle;
+
+pub(super) const EVENT_STREAM_KEY: &str = "__teo_event_stream";
+
+pub(super) const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+
+/// A single server-sent event carrying a Teon value as its data.
+#[derive(Debug, Clone)]
+pub struct SseEvent {
+    data: Value,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl SseEvent {
+
+    pub fn new(data: impl Into<Value>) -> Self {
+        Self {
+            data: data.into(),
+            event: None,
+            id: None,
+            retry: None,
+        }
+    }
+
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Tell the client how long to wait before reconnecting.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    fn encode(&self) -> Result<Bytes> {
+        let mut encoded = String::new();
+        if let Some(event) = &self.event {
+            writeln!(encoded, "event: {}", single_line(event)).unwrap();
+        }
+        if let Some(id) = &self.id {
+            writeln!(encoded, "id: {}", single_line(id)).unwrap();
+        }
+        if let Some(retry) = self.retry {
+            writeln!(encoded, "retry: {}", retry.as_millis()).unwrap();
+        }
+        let Ok(json_value) = JsonValue::try_from(&self.data) else {
+            return Err(Error::internal_server_error_message("cannot convert event data to json"));
+        };
+        writeln!(encoded, "data: {}", serde_json::to_string(&json_value).unwrap()).unwrap();
+        encoded.push('\n');
+        Ok(Bytes::from(encoded))
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// A stream of server-sent events which a handler returns with `into_response`.
+pub struct EventStream {
+    stream: Pin<Box<dyn Stream<Item = SseEvent> + Send>>,
+    heartbeat: Option<Duration>,
+}
+
+impl EventStream {
+
+    pub fn new<S>(stream: S) -> Self where S: Stream<Item = SseEvent> + Send + 'static {
+        Self {
+            stream: Box::pin(stream),
+            heartbeat: Some(Duration::from_secs(15)),
+        }
+    }
+
+    /// Create an event stream fed by the returned sender. The sender fails once the client disconnects.
+    pub fn channel(buffer: usize) -> (EventSender, Self) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|event| (event, receiver))
+        });
+        (EventSender { sender }, Self::new(stream))
+    }
+
+    /// Send a comment line when no event was sent for this long, `None` disables heartbeats.
+    pub fn heartbeat(mut self, heartbeat: Option<Duration>) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    pub fn into_response(self, request: &Request) -> Result<Response> {
+        request.local_objects().insert(EVENT_STREAM_KEY, Mutex::new(Some(self)));
+        let response = Response::empty();
+        response.headers().insert("content-type", EVENT_STREAM_CONTENT_TYPE)?;
+        response.headers().insert("cache-control", "no-cache")?;
+        Ok(response)
+    }
+}
+
+/// Pushes events into an `EventStream` created with `EventStream::channel`.
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    sender: mpsc::Sender<SseEvent>,
+}
+
+impl EventSender {
+
+    pub async fn send(&self, event: SseEvent) -> Result<()> {
+        self.sender.send(event).await.map_err(|_| Error::new("event stream client disconnected"))
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Resolves when the client disconnects.
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+}
+
+/// The HTTP body of an `EventStream`. Dropping it, which happens when the client disconnects,
+/// drops the event stream and closes its sender. The body ends when the server shuts down so the
+/// connection can drain.
+pub struct EventStreamBody {
+    stream: Pin<Box<dyn Stream<Item = SseEvent> + Send>>,
+    heartbeat: Option<Interval>,
+    shutdown: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl EventStreamBody {
+    pub(super) fn new(event_stream: EventStream, shutdown_handle: Option<ShutdownHandle>) -> Self {
+        Self {
+            stream: event_stream.stream,
+            heartbeat: event_stream.heartbeat.map(|period| interval_at(Instant::now() + period, period)),
+            shutdown: shutdown_handle.map(|shutdown_handle| {
+                let mut receiver = shutdown_handle.subscribe();
+                let shutdown: Pin<Box<dyn Future<Output = ()> + Send>> = Box::pin(async move {
+                    let _ = receiver.wait_for(|shutdown| *shutdown).await;
+                });
+                shutdown
+            }),
+        }
+    }
+}
+
+impl Body for EventStreamBody {
+    type Data = Bytes;
+    /// An event whose data can't be sent aborts the stream instead of sending something else
+    type Error = std::io::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<std::result::Result<Frame<Self::Data>, Self::Error>>> {
+        if let Some(shutdown) = self.shutdown.as_mut() {
+            if shutdown.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+        }
+        match self.stream.poll_next_unpin(cx) {
+            Poll::Ready(Some(event)) => {
+                if let Some(heartbeat) = self.heartbeat.as_mut() {
+                    heartbeat.reset();
+                }
+                Poll::Ready(Some(match event.encode() {
+                    Ok(encoded) => Ok(Frame::data(encoded)),
+                    Err(err) => Err(std::io::Error::other(err.message().to_owned())),
+                }))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => match self.heartbeat.as_mut() {
+                Some(heartbeat) => match heartbeat.poll_tick(cx) {
+                    Poll::Ready(_) => Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(b":\n\n"))))),
+                    Poll::Pending => Poll::Pending,
+                },
+                None => Poll::Pending,
+            },
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::io;
+use std::os::fd::{FromRawFd, IntoRawFd, RawFd};
+use teo_result::{Error, Result};
+use tokio::net::{TcpListener, UnixListener};
+use crate::server::listener::Listener;
+
+/// The first file descriptor passed by the service manager, see `sd_listen_fds(3)`.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Adopt the sockets systemd passed to this process, with a description of each for the start message.
+///
+/// The `LISTEN_*` variables are left in place, changing the environment isn't safe once other
+/// threads run, and child processes ignore them since `LISTEN_PID` names this process.
+pub(super) fn inherited_listeners() -> Result<Vec<(Listener, String)>> {
+    let Ok(pid) = std::env::var("LISTEN_PID") else {
+        return Ok(vec![]);
+    };
+    // the variables are meant for the process systemd started, not for its children
+    if pid.parse::<u32>().ok() != Some(std::process::id()) {
+        return Ok(vec![]);
+    }
+    let count = match std::env::var("LISTEN_FDS").map(|count| count.parse::<RawFd>()) {
+        Ok(Ok(count)) => count,
+        _ => return Err(Error::new("cannot parse LISTEN_FDS")),
+    };
+    (LISTEN_FDS_START..LISTEN_FDS_START + count).map(adopt).collect()
+}
+
+fn adopt(fd: RawFd) -> Result<(Listener, String)> {
+    // adopting a descriptor which isn't a listening stream socket would fail on the first accept
+    match (socket_option(fd, libc::SO_TYPE), socket_option(fd, libc::SO_ACCEPTCONN)) {
+        (Ok(libc::SOCK_STREAM), Ok(accepting)) if accepting != 0 => (),
+        (Err(err), _) | (_, Err(err)) => return Err(Error::new(format!("inherited file descriptor {} is not a socket: {}", fd, err))),
+        _ => return Err(Error::new(format!("inherited file descriptor {} is not a listening stream socket", fd))),
+    }
+    // a Unix socket has no IP address, which is how the two kinds are told apart
+    let tcp_listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+    if let Ok(addr) = tcp_listener.local_addr() {
+        tcp_listener.set_nonblocking(true)?;
+        return Ok((Listener::Tcp(TcpListener::from_std(tcp_listener)?), format!("{} (systemd fd {})", addr, fd)));
+    }
+    let unix_listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(tcp_listener.into_raw_fd()) };
+    let addr = match unix_listener.local_addr() {
+        Ok(addr) => addr,
+        Err(err) => return Err(Error::new(format!("cannot read the address of inherited file descriptor {}: {}", fd, err))),
+    };
+    unix_listener.set_nonblocking(true)?;
+    let description = match addr.as_pathname() {
+        Some(path) => format!("unix:{} (systemd fd {})", path.display(), fd),
+        None => format!("unix socket (systemd fd {})", fd),
+    };
+    Ok((Listener::Unix(UnixListener::from_std(unix_listener)?, None), description))
+}
+
+fn socket_option(fd: RawFd, name: libc::c_int) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut length = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, name, &mut value as *mut libc::c_int as *mut libc::c_void, &mut length) };
+    if result == -1 { Err(io::Error::last_os_error()) } else { Ok(value) }
+}
@@ -1,6 +1,6 @@
 use bytes::Bytes;
 use http_body_util::{BodyExt, Full};
-use hyper::Method;
+use hyper::{Method, Version};
 use hyper::body::Body;
 use hyper::header::HeaderValue;
 use teo_result::{Error, Result};
@@ -11,6 +11,7 @@ use teo_runtime::headers::Headers;
 pub struct TestRequest {
     method: Method,
     uri: String,
+    version: Version,
     headers: Headers,
     cookies: Cookies,
     body: Full<Bytes>,
@@ -21,6 +22,7 @@ impl TestRequest {
         Self {
             method,
             uri: uri.to_string(),
+            version: Version::HTTP_11,
             headers: Headers::new(),
             cookies: Cookies::new(),
             body: Full::new(Bytes::new()),
@@ -53,6 +55,15 @@ impl TestRequest {
         &self.uri
     }
 
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn set_version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
     pub fn insert_header<K, V>(self, key: K, value: V) -> Result<Self> where K: Into<String>, V: Into<String> {
         self.headers.insert(key, value)?;
         Ok(self)
@@ -84,7 +95,8 @@ impl TestRequest {
         let cookies = self.cookies().clone();
         let request = hyper::Request::builder()
             .method(self.method)
-            .uri(self.uri);
+            .uri(self.uri)
+            .version(self.version);
         let mut request = request.body(self.body).unwrap();
         headers.extend_to(request.headers_mut());
         for cookie in cookies {
@@ -1,10 +1,13 @@
+use std::sync::Arc;
 use bytes::Bytes;
-use http_body_util::{BodyExt, Either, Full};
+use http_body_util::{BodyExt, Either};
 use hyper::{StatusCode, Version};
-use tower_http::services::fs::ServeFileSystemResponseBody;
 use teo_result::{Result, Error};
 use teo_runtime::cookies::Cookies;
 use teo_runtime::headers::Headers;
+use tokio::sync::Mutex;
+use crate::server::response::HyperResponseBody;
+use crate::server::sse::EventStreamBody;
 
 #[derive(Clone)]
 pub struct TestResponse {
@@ -13,15 +16,20 @@ pub struct TestResponse {
     headers: Headers,
     body: Bytes,
     cookies: Cookies,
+    event_stream: Option<Arc<Mutex<TestEventStream>>>,
 }
 
 impl TestResponse {
 
-    pub(crate) async fn new(hyper_response: hyper::Response<Either<Full<Bytes>, ServeFileSystemResponseBody>>) -> Result<Self> {
+    pub(crate) async fn new(hyper_response: hyper::Response<HyperResponseBody>) -> Result<Self> {
         let (parts, body) = hyper_response.into_parts();
-        let body = match body.collect().await {
-            Ok(body) => body.to_bytes(),
-            Err(_) => return Err(Error::internal_server_error_message("cannot read test response body")),
+        let (body, event_stream) = match body {
+            // an event stream may never end, its events are read on demand
+            Either::Right(Either::Right(event_stream)) => (Bytes::new(), Some(Arc::new(Mutex::new(TestEventStream::new(event_stream))))),
+            body => match body.collect().await {
+                Ok(body) => (body.to_bytes(), None),
+                Err(_) => return Err(Error::internal_server_error_message("cannot read test response body")),
+            },
         };
         let headers = Headers::from(parts.headers);
         let cookies = Cookies::from_response_headers(&headers)?;
@@ -31,6 +39,7 @@ impl TestResponse {
             headers,
             cookies,
             body,
+            event_stream,
         })
     }
 
@@ -65,4 +74,92 @@ impl TestResponse {
     pub fn body_as_string(&self) -> String {
         unsafe { String::from_utf8_unchecked(self.body.to_vec()) }
     }
-}
\ No newline at end of file
+
+    /// Read the next `count` server-sent events, fewer are returned if the stream ends first.
+    pub async fn events(&self, count: usize) -> Result<Vec<TestEvent>> {
+        let Some(event_stream) = self.event_stream.as_ref() else {
+            return Err(Error::internal_server_error_message("test response is not an event stream"));
+        };
+        event_stream.lock().await.next_events(count).await
+    }
+
+    /// The number of heartbeat comments received while reading events.
+    pub async fn heartbeats(&self) -> usize {
+        match self.event_stream.as_ref() {
+            Some(event_stream) => event_stream.lock().await.heartbeats,
+            None => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+    pub data: String,
+}
+
+impl TestEvent {
+    pub fn data_as_json(&self) -> Result<serde_json::Value> {
+        serde_json::from_str(&self.data).map_err(|_| Error::internal_server_error_message("incorrect json format"))
+    }
+}
+
+struct TestEventStream {
+    body: EventStreamBody,
+    buffer: String,
+    heartbeats: usize,
+}
+
+impl TestEventStream {
+
+    fn new(body: EventStreamBody) -> Self {
+        Self { body, buffer: String::new(), heartbeats: 0 }
+    }
+
+    async fn next_events(&mut self, count: usize) -> Result<Vec<TestEvent>> {
+        let mut events = vec![];
+        while events.len() < count {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                match parse_event(&block) {
+                    Some(event) => events.push(event),
+                    None => self.heartbeats += 1,
+                }
+                continue;
+            }
+            match self.body.frame().await {
+                Some(Ok(frame)) => if let Ok(data) = frame.into_data() {
+                    self.buffer.push_str(&String::from_utf8_lossy(&data));
+                },
+                Some(Err(err)) => return Err(Error::new(format!("event stream failed: {}", err))),
+                None => break,
+            }
+        }
+        Ok(events)
+    }
+}
+
+fn parse_event(block: &str) -> Option<TestEvent> {
+    let mut event = TestEvent { event: None, id: None, retry: None, data: String::new() };
+    let mut has_data = false;
+    for line in block.lines() {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event.event = Some(value.to_owned()),
+            "id" => event.id = Some(value.to_owned()),
+            "retry" => event.retry = value.parse().ok(),
+            "data" => {
+                if has_data {
+                    event.data.push('\n');
+                }
+                event.data.push_str(value);
+                has_data = true;
+            }
+            _ => (),
+        }
+    }
+    has_data.then_some(event)
+}
@@ -0,0 +1,52 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value as JsonValue;
+use teo_result::{Error, Result};
+use tokio::io::DuplexStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use crate::server::websocket::WebSocketMessage;
+
+/// The client side of a WebSocket opened with `Server::process_test_websocket`.
+pub struct TestWebSocket {
+    stream: WebSocketStream<DuplexStream>,
+}
+
+impl TestWebSocket {
+
+    pub(crate) fn new(stream: WebSocketStream<DuplexStream>) -> Self {
+        Self { stream }
+    }
+
+    pub async fn send(&mut self, message: WebSocketMessage) -> Result<()> {
+        self.stream.send(message.into_message()).await.map_err(|err| Error::new(format!("websocket error: {}", err)))
+    }
+
+    pub async fn send_json(&mut self, value: &JsonValue) -> Result<()> {
+        self.send(WebSocketMessage::json(value)).await
+    }
+
+    /// Receive the next data message, `None` once the server closed the connection.
+    pub async fn recv(&mut self) -> Result<Option<WebSocketMessage>> {
+        while let Some(message) = self.stream.next().await {
+            match message {
+                Ok(Message::Close(_)) => return Ok(None),
+                Ok(message) => if let Some(message) = WebSocketMessage::from_message(message) {
+                    return Ok(Some(message));
+                },
+                Err(err) => return Err(Error::new(format!("websocket error: {}", err))),
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn recv_json(&mut self) -> Result<JsonValue> {
+        match self.recv().await? {
+            Some(message) => message.as_json(),
+            None => Err(Error::new("websocket closed")),
+        }
+    }
+
+    pub async fn close(mut self) -> Result<()> {
+        self.stream.close(None).await.map_err(|err| Error::new(format!("websocket error: {}", err)))
+    }
+}
@@ -0,0 +1,28 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use teo_result::Error;
+use teo_runtime::handler::r#match::HandlerMatch;
+use crate::server::utils::handler_key;
+
+#[derive(Debug, Clone, Default)]
+pub struct TimeoutConfig {
+    /// Deadline for handling a request, `None`, the default, lets requests run forever
+    pub default: Option<Duration>,
+    /// Deadlines overridden by handler or model action, keyed by dotted handler path like `User.create`
+    pub handlers: BTreeMap<String, Option<Duration>>,
+}
+
+impl TimeoutConfig {
+    pub fn for_handler(&self, handler_match: &HandlerMatch) -> Option<Duration> {
+        match self.handlers.get(&handler_key(handler_match)) {
+            Some(timeout) => *timeout,
+            None => self.default,
+        }
+    }
+}
+
+pub(super) fn gateway_timeout(timeout: Duration) -> Error {
+    let mut error = Error::new(format!("request timed out after {}ms", timeout.as_millis()));
+    error.code = 504;
+    error
+}
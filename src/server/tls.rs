@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use teo_result::{Error, Result};
+use tokio_rustls::rustls::{self, RootCertStore};
+use tokio_rustls::rustls::crypto::ring::default_provider;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::TlsAcceptor;
+use crate::server::config::HttpProtocol;
+
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM file containing the server certificate chain
+    pub cert: PathBuf,
+    /// PEM file containing the server private key
+    pub key: PathBuf,
+    /// PEM file containing the CAs which client certificates are verified against
+    pub client_ca: Option<PathBuf>,
+    /// Whether clients without a certificate are still accepted when `client_ca` is set
+    pub client_auth_optional: bool,
+    /// ALPN protocols to advertise, derived from the server protocol if empty
+    pub alpn: Vec<String>,
+}
+
+impl TlsConfig {
+    pub fn new(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        Self {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca: None,
+            client_auth_optional: false,
+            alpn: vec![],
+        }
+    }
+}
+
+pub(super) fn tls_acceptor(config: &TlsConfig, protocol: HttpProtocol) -> Result<TlsAcceptor> {
+    let provider = Arc::new(default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|err| Error::new(format!("cannot configure TLS: {}", err)))?;
+    let builder = if let Some(client_ca) = &config.client_ca {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(client_ca)? {
+            roots.add(cert).map_err(|err| Error::new(format!("invalid client CA certificate in {}: {}", client_ca.display(), err)))?;
+        }
+        let mut verifier_builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+        if config.client_auth_optional {
+            verifier_builder = verifier_builder.allow_unauthenticated();
+        }
+        let verifier = verifier_builder.build().map_err(|err| Error::new(format!("cannot configure client certificate verification: {}", err)))?;
+        builder.with_client_cert_verifier(verifier)
+    } else {
+        builder.with_no_client_auth()
+    };
+    let mut server_config = builder
+        .with_single_cert(load_certs(&config.cert)?, load_private_key(&config.key)?)
+        .map_err(|err| Error::new(format!("invalid TLS certificate or key: {}", err)))?;
+    server_config.alpn_protocols = if config.alpn.is_empty() {
+        match protocol {
+            HttpProtocol::Http1 => vec![b"http/1.1".to_vec()],
+            HttpProtocol::Http2 => vec![b"h2".to_vec()],
+            HttpProtocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        }
+    } else {
+        config.alpn.iter().map(|p| p.as_bytes().to_vec()).collect()
+    };
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).map_err(|err| Error::new(format!("cannot open certificate file {}: {}", path.display(), err)))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|err| Error::new(format!("cannot read certificate file {}: {}", path.display(), err)))?;
+    if certs.is_empty() {
+        return Err(Error::new(format!("no certificate found in {}", path.display())));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).map_err(|err| Error::new(format!("cannot open private key file {}: {}", path.display(), err)))?;
+    match rustls_pemfile::private_key(&mut BufReader::new(file)) {
+        Ok(Some(key)) => Ok(key),
+        Ok(None) => Err(Error::new(format!("no private key found in {}", path.display()))),
+        Err(err) => Err(Error::new(format!("cannot read private key file {}: {}", path.display(), err))),
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer as _, TracerProvider as _};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use teo_result::{Error, Result};
+use teo_runtime::middleware::next::{Next, NextImp};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use crate::server::utils::MatchedHandler;
+
+const TRACE_SPAN_KEY: &str = "__teo_trace_span";
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+const TRACERESPONSE: &str = "traceresponse";
+
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    /// Reported as the `service.name` resource attribute
+    pub service_name: String,
+    pub exporter: TraceExporter,
+    /// Add a `traceresponse` header naming the server span, off by default since it reveals
+    /// trace ids to clients
+    pub response_header: bool,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            service_name: "teo".to_owned(),
+            exporter: TraceExporter::OtlpHttp { endpoint: "http://localhost:4318/v1/traces".to_owned() },
+            response_header: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceExporter {
+    /// Export spans in batches to an OpenTelemetry collector over OTLP/HTTP
+    OtlpHttp { endpoint: String },
+    /// Print every span when it ends, for development
+    Stdout,
+}
+
+#[derive(Debug)]
+pub(super) struct Tracing {
+    provider: TracerProvider,
+    tracer: Tracer,
+}
+
+impl Tracing {
+
+    pub(super) fn new(config: &TraceConfig) -> Result<Self> {
+        let builder = match &config.exporter {
+            TraceExporter::OtlpHttp { endpoint } => {
+                let exporter = match opentelemetry_otlp::SpanExporter::builder().with_http().with_endpoint(endpoint).build() {
+                    Ok(exporter) => exporter,
+                    Err(err) => return Err(Error::new(format!("cannot create OTLP exporter: {}", err))),
+                };
+                TracerProvider::builder().with_batch_exporter(exporter, runtime::Tokio)
+            }
+            TraceExporter::Stdout => TracerProvider::builder().with_simple_exporter(opentelemetry_stdout::SpanExporter::default()),
+        };
+        let provider = builder.with_resource(Resource::new([KeyValue::new("service.name", config.service_name.clone())])).build();
+        let tracer = provider.tracer("teo");
+        Ok(Self { provider, tracer })
+    }
+
+    /// Start the server span of a request, continuing the trace of an incoming `traceparent`.
+    pub(super) fn start(&self, request: &Request) -> Result<TraceSpan> {
+        let mut carrier = HashMap::new();
+        for name in [TRACEPARENT, TRACESTATE] {
+            if let Some(value) = request.headers().get(name)? {
+                carrier.insert(name.to_owned(), value.to_string());
+            }
+        }
+        let parent = TraceContextPropagator::new().extract(&carrier);
+        let span = self.tracer.span_builder(format!("{} {}", request.method(), request.path()))
+            .with_kind(SpanKind::Server)
+            .with_attributes([
+                KeyValue::new("http.request.method", request.method().to_string()),
+                KeyValue::new("url.path", request.path().to_owned()),
+            ])
+            .start_with_context(&self.tracer, &parent);
+        let trace_span = TraceSpan { tracer: self.tracer.clone(), context: parent.with_span(span) };
+        request.local_objects().insert(TRACE_SPAN_KEY, trace_span.clone());
+        Ok(trace_span)
+    }
+
+    /// Flush the spans which are not exported yet.
+    pub(super) fn shutdown(&self) {
+        if let Err(err) = self.provider.shutdown() {
+            eprintln!("Error shutting down tracing: {:?}", err);
+        }
+    }
+}
+
+/// A span of a request, children are created explicitly since requests run across tasks.
+#[derive(Debug, Clone)]
+pub(super) struct TraceSpan {
+    tracer: Tracer,
+    context: Context,
+}
+
+impl TraceSpan {
+
+    pub(super) fn child(&self, name: &'static str, attributes: Vec<KeyValue>) -> TraceSpan {
+        let span = self.tracer.span_builder(name).with_attributes(attributes).start_with_context(&self.tracer, &self.context);
+        TraceSpan { tracer: self.tracer.clone(), context: self.context.with_span(span) }
+    }
+
+    pub(super) fn end<T>(&self, result: &Result<T>) {
+        let span = self.context.span();
+        if let Err(error) = result {
+            span.set_attribute(KeyValue::new("http.response.status_code", error.code as i64));
+            span.set_status(Status::error(error.message().to_owned()));
+        }
+        span.end();
+    }
+
+    /// End the server span, with `response_header` its context is returned in `traceresponse`.
+    pub(super) fn finish<B>(&self, handler: Option<MatchedHandler>, response_header: bool, response: &mut hyper::Response<B>) {
+        let span = self.context.span();
+        if let Some(handler) = handler {
+            let mut names = handler.namespace.clone();
+            names.extend(handler.model.clone());
+            names.push(handler.action.clone());
+            span.update_name(names.join("."));
+            span.set_attributes(handler_attributes(&handler));
+        }
+        let status = response.status();
+        span.set_attribute(KeyValue::new("http.response.status_code", status.as_u16() as i64));
+        if status.is_server_error() {
+            span.set_status(Status::error(status.to_string()));
+        }
+        if response_header {
+            // `traceresponse` has the format of `traceparent`, see W3C Trace Context Level 2
+            let mut carrier: HashMap<String, String> = HashMap::new();
+            TraceContextPropagator::new().inject_context(&self.context, &mut carrier);
+            if let Some(value) = carrier.remove(TRACEPARENT).and_then(|value| hyper::header::HeaderValue::try_from(value).ok()) {
+                response.headers_mut().insert(TRACERESPONSE, value);
+            }
+        }
+        span.end();
+    }
+}
+
+/// The server span stored on the request by `Tracing::start`.
+pub(super) fn request_span(request: &Request) -> Option<TraceSpan> {
+    request.local_objects().get::<TraceSpan>(TRACE_SPAN_KEY).cloned()
+}
+
+pub(super) fn handler_attributes(handler: &MatchedHandler) -> Vec<KeyValue> {
+    let mut attributes = vec![
+        KeyValue::new("teo.namespace", handler.namespace.join(".")),
+        KeyValue::new("teo.action", handler.action.clone()),
+    ];
+    if let Some(model) = handler.model.as_ref() {
+        attributes.push(KeyValue::new("teo.model", model.clone()));
+    }
+    attributes
+}
+
+/// Run `next` in a child span of `parent`.
+pub(super) fn traced_next(next: Next, parent: Option<&TraceSpan>, name: &'static str, attributes: Vec<KeyValue>) -> Next {
+    let Some(parent) = parent.cloned() else {
+        return next;
+    };
+    Next::new(move |request: Request| {
+        let next = next.clone();
+        let span = parent.child(name, attributes.clone());
+        async move {
+            let result: Result<Response> = next.call(request).await;
+            span.end(&result);
+            result
+        }
+    })
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeSet;
+use hyper::Method;
+use teo_runtime::handler::r#match::HandlerMatch;
+use teo_runtime::namespace::Namespace;
+use crate::server::handler_found::{find_handler, HandlerFound};
+use crate::server::utils::handler_key;
+
+const BUILTIN_WRITE_ACTIONS: [&str; 9] = [
+    "create", "update", "upsert", "delete", "copy",
+    "createMany", "updateMany", "deleteMany", "copyMany",
+];
+
+/// Which requests run in a transaction.
+///
+/// A transactional request is created with the ctx of its transaction, so request middlewares,
+/// handler middlewares and the action share one request. Handlers opt in by path here: the schema
+/// has no `@transactional` decorator yet, and transactions start at the database's default
+/// isolation level since the connectors take no other.
+#[derive(Debug, Clone)]
+pub struct TransactionConfig {
+    /// Run create, update, upsert, delete, copy and their `Many` variants in a transaction
+    pub builtin_writes: bool,
+    /// Custom handlers which run in a transaction too, keyed by dotted handler path like `User.signIn`
+    pub handlers: BTreeSet<String>,
+}
+
+impl Default for TransactionConfig {
+    fn default() -> Self {
+        Self {
+            builtin_writes: true,
+            handlers: BTreeSet::new(),
+        }
+    }
+}
+
+impl TransactionConfig {
+    fn is_transactional(&self, handler_found: &HandlerFound, handler_match: &HandlerMatch) -> bool {
+        match handler_found {
+            HandlerFound::Builtin(_, _) => self.builtin_writes && BUILTIN_WRITE_ACTIONS.contains(&handler_match.handler_name()),
+            HandlerFound::Custom(_) => self.handlers.contains(&handler_key(handler_match)),
+        }
+    }
+
+    /// Whether the request to `path` is routed to a handler which runs in a transaction, decided
+    /// before the request is created.
+    pub(super) fn is_transactional_route(&self, main_namespace: &Namespace, method: &Method, path: &str) -> bool {
+        let path_prefix = main_namespace.server().and_then(|server| server.path_prefix.clone());
+        let path = match path_prefix.as_deref().map(|prefix| prefix.trim_end_matches('/')) {
+            Some(prefix) => match path.strip_prefix(prefix) {
+                Some("") => "/",
+                Some(path) => path,
+                None => return false,
+            },
+            None => path,
+        };
+        let Some(handler_match) = main_namespace.handler_map().match_all(method, path) else {
+            return false;
+        };
+        match find_handler(main_namespace, &handler_match) {
+            Some((_, handler_found)) => self.is_transactional(&handler_found, &handler_match),
+            None => false,
+        }
+    }
+}
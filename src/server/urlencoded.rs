@@ -0,0 +1,137 @@
+use serde_json::{Map, Value as JsonValue};
+use teo_result::{Error, Result};
+use crate::server::limits::{payload_too_large, raw_json_depth_exceeds};
+
+/// Parse an `application/x-www-form-urlencoded` body into the JSON shape multipart form bodies
+/// produce. `a[]` appends to an array and `a[b]` sets a key of an object, brackets may nest up
+/// to `max_depth` levels.
+pub(super) fn parse_urlencoded(body: &[u8], max_depth: usize) -> Result<JsonValue> {
+    let mut result = JsonValue::Object(Map::new());
+    for (key, value) in form_urlencoded::parse(body) {
+        insert_bracket_key(&mut result, &key, JsonValue::String(value.into_owned()), max_depth)?;
+    }
+    Ok(result)
+}
+
+/// Parse a query string into a JSON value for GET and DELETE handlers. Brackets work like in
+/// form bodies, a repeated plain key becomes an array, and a JSON encoded `q` parameter supplies
+/// typed arguments which the other parameters are merged onto.
+///
+/// Values which are JSON numbers, booleans or `null` keep their type so that `take=10` is an
+/// integer, a string which looks like one of them is passed quoted, like `name="10"`.
+pub(super) fn parse_query(query: &str, max_depth: usize) -> Result<JsonValue> {
+    let mut result = JsonValue::Object(Map::new());
+    let mut q = None;
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        if key == "q" {
+            q = Some(value.into_owned());
+            continue;
+        }
+        let value = query_value(value.into_owned());
+        if !key.contains('[') {
+            if let Some(existing) = result.as_object_mut().unwrap().get_mut(key.as_ref()) {
+                match existing {
+                    JsonValue::Array(array) => array.push(value),
+                    existing => *existing = JsonValue::Array(vec![existing.take(), value]),
+                }
+                continue;
+            }
+        }
+        insert_bracket_key(&mut result, &key, value, max_depth)?;
+    }
+    if let Some(q) = q {
+        if raw_json_depth_exceeds(q.as_bytes(), max_depth) {
+            return Err(payload_too_large(format!("query parameter `q` exceeds the nesting depth limit of {}", max_depth)));
+        }
+        let Ok(JsonValue::Object(mut base)) = serde_json::from_str::<JsonValue>(&q) else {
+            return Err(Error::invalid_request_message("query parameter `q` should be a JSON object"));
+        };
+        base.extend(std::mem::take(result.as_object_mut().unwrap()));
+        result = JsonValue::Object(base);
+    }
+    if result.as_object().unwrap().is_empty() {
+        return Ok(JsonValue::Null);
+    }
+    Ok(result)
+}
+
+fn query_value(value: String) -> JsonValue {
+    match serde_json::from_str::<JsonValue>(&value) {
+        Ok(typed @ (JsonValue::Number(_) | JsonValue::Bool(_) | JsonValue::Null | JsonValue::String(_))) => typed,
+        _ => JsonValue::String(value),
+    }
+}
+
+/// Insert `value` at the path described by a bracketed key like `a[b][]`.
+pub(super) fn insert_bracket_key(root: &mut JsonValue, key: &str, value: JsonValue, max_depth: usize) -> Result<()> {
+    let segments = key_segments(key, max_depth)?;
+    let mut current = root;
+    for (index, segment) in segments.iter().enumerate() {
+        let last = index == segments.len() - 1;
+        current = match segment {
+            KeySegment::Push => {
+                let Some(array) = current.as_array_mut() else {
+                    return Err(conflicting_key(key));
+                };
+                if last {
+                    array.push(value);
+                    return Ok(());
+                }
+                array.push(empty_container(&segments[index + 1]));
+                array.last_mut().unwrap()
+            }
+            KeySegment::Key(name) => {
+                let Some(object) = current.as_object_mut() else {
+                    return Err(conflicting_key(key));
+                };
+                if last {
+                    object.insert(name.to_string(), value);
+                    return Ok(());
+                }
+                object.entry(name.to_string()).or_insert_with(|| empty_container(&segments[index + 1]))
+            }
+        };
+    }
+    Ok(())
+}
+
+enum KeySegment<'a> {
+    Key(&'a str),
+    Push,
+}
+
+/// Split a key into its segments, refusing more than `max_depth` of them before building anything,
+/// each one adds a level of nesting.
+fn key_segments(key: &str, max_depth: usize) -> Result<Vec<KeySegment>> {
+    let (name, mut rest) = match key.find('[') {
+        Some(index) => (&key[..index], &key[index..]),
+        None => (key, ""),
+    };
+    if name.is_empty() {
+        return Err(Error::invalid_request_message(format!("invalid field name: {}", key)));
+    }
+    let mut segments = vec![KeySegment::Key(name)];
+    while !rest.is_empty() {
+        if segments.len() >= max_depth {
+            return Err(payload_too_large(format!("field name `{}` exceeds the nesting depth limit of {}", name, max_depth)));
+        }
+        let Some(end) = rest.strip_prefix('[').and_then(|r| r.find(']')) else {
+            return Err(Error::invalid_request_message(format!("invalid field name: {}", key)));
+        };
+        let inner = &rest[1..end + 1];
+        segments.push(if inner.is_empty() { KeySegment::Push } else { KeySegment::Key(inner) });
+        rest = &rest[end + 2..];
+    }
+    Ok(segments)
+}
+
+fn empty_container(next: &KeySegment) -> JsonValue {
+    match next {
+        KeySegment::Push => JsonValue::Array(vec![]),
+        KeySegment::Key(_) => JsonValue::Object(Map::new()),
+    }
+}
+
+fn conflicting_key(key: &str) -> Error {
+    Error::invalid_request_message(format!("conflicting field name: {}", key))
+}
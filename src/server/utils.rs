@@ -1,3 +1,7 @@
+use teo_runtime::handler::r#match::HandlerMatch;
+use teo_runtime::namespace::Namespace;
+use teo_runtime::request::Request;
+
 pub fn remove_path_prefix<'a>(path: &'a str, prefix: Option<&String>) -> &'a str {
     if let Some(prefix) = prefix {
         let prefix = prefix.as_str();
@@ -13,3 +17,33 @@ pub fn remove_path_prefix<'a>(path: &'a str, prefix: Option<&String>) -> &'a str
         path
     }
 }
+
+pub fn handler_key(handler_match: &HandlerMatch) -> String {
+    let mut key = handler_match.path().join(".");
+    if !key.is_empty() {
+        key.push('.');
+    }
+    key.push_str(handler_match.handler_name());
+    key
+}
+
+pub(crate) struct MatchedHandler {
+    pub(crate) namespace: Vec<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) action: String,
+}
+
+pub(crate) fn matched_handler(main_namespace: &Namespace, request: &Request) -> Option<MatchedHandler> {
+    let handler_match = request.handler_match().ok()?;
+    // the last path item names a model or handler group unless it's a namespace itself
+    let (namespace, model) = if main_namespace.namespace_at_path(&handler_match.path()).is_some() {
+        (handler_match.path(), None)
+    } else {
+        (handler_match.path_without_last(), Some(handler_match.group_name().to_owned()))
+    };
+    Some(MatchedHandler {
+        namespace: namespace.iter().map(|name| name.to_string()).collect(),
+        model,
+        action: handler_match.handler_name().to_owned(),
+    })
+}
@@ -0,0 +1,194 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use futures_util::{SinkExt, StreamExt};
+use hyper::body::Incoming;
+use hyper::header::{CONNECTION, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper::Method;
+use hyper_util::rt::TokioIo;
+use serde_json::Value as JsonValue;
+use teo_result::{Error, Result};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::value::Value;
+use crate::server::shutdown::request_shutdown_handle;
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Role};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+const ON_UPGRADE_KEY: &str = "__teo_on_upgrade";
+
+const SESSION_KEY: &str = "__teo_websocket_session";
+
+type Session = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Keep the upgrade future of a WebSocket handshake request so that a handler can accept it.
+pub(super) fn prepare_upgrade(hyper_request: &mut hyper::Request<Incoming>) -> Option<OnUpgrade> {
+    let upgrade = hyper_request.headers().get(UPGRADE)?;
+    if !upgrade.as_bytes().eq_ignore_ascii_case(b"websocket") {
+        return None;
+    }
+    Some(hyper::upgrade::on(hyper_request))
+}
+
+pub(super) fn store_upgrade(request: &Request, on_upgrade: OnUpgrade) {
+    request.local_objects().insert(ON_UPGRADE_KEY, Mutex::new(Some(on_upgrade)));
+}
+
+/// Start the session of an accepted handshake once its `101` response went through every
+/// middleware. A middleware which replaced the response leaves the connection as it is.
+pub(super) fn start_session(request: &Request) {
+    let Some(session) = request.local_objects()
+        .get::<Mutex<Option<Session>>>(SESSION_KEY)
+        .and_then(|slot| slot.lock().unwrap().take()) else {
+        return;
+    };
+    // the server waits for sessions to close when it shuts down
+    match request_shutdown_handle(request) {
+        Some(shutdown_handle) => shutdown_handle.spawn(session),
+        None => { tokio::spawn(session); }
+    }
+}
+
+/// Accept a WebSocket handshake and run `callback` with the socket once the connection is upgraded.
+///
+/// Call this from a handler, the request has already passed the request and handler middleware
+/// stacks at this point, so authentication applies to WebSocket connections as well. The session
+/// starts after the returned `101` response is sent.
+pub fn upgrade<F, Fut>(request: &Request, callback: F) -> Result<Response> where
+    F: FnOnce(WebSocket) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static {
+    if request.method() != Method::GET {
+        return Err(Error::invalid_request_message("websocket handshake requires GET"));
+    }
+    if request.headers().get(SEC_WEBSOCKET_VERSION.as_str())?.map(|v| v.to_string()).as_deref() != Some("13") {
+        return Err(Error::invalid_request_message("unsupported websocket version"));
+    }
+    let Some(key) = request.headers().get(SEC_WEBSOCKET_KEY.as_str())?.map(|k| k.to_string()) else {
+        return Err(Error::invalid_request_message("missing websocket key"));
+    };
+    let on_upgrade = request.local_objects()
+        .get::<Mutex<Option<OnUpgrade>>>(ON_UPGRADE_KEY)
+        .and_then(|slot| slot.lock().unwrap().take());
+    let Some(on_upgrade) = on_upgrade else {
+        return Err(Error::invalid_request_message("connection cannot be upgraded to websocket"));
+    };
+    let shutdown = request_shutdown_handle(request).map(|shutdown_handle| shutdown_handle.subscribe());
+    let session: Session = Box::pin(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let stream = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+                callback(WebSocket { stream, shutdown }).await
+            }
+            Err(err) => eprintln!("Error upgrading connection: {:?}", err),
+        }
+    });
+    request.local_objects().insert(SESSION_KEY, Mutex::new(Some(session)));
+    let response = Response::empty();
+    response.set_code(101);
+    response.headers().insert(UPGRADE.as_str(), "websocket")?;
+    response.headers().insert(CONNECTION.as_str(), "upgrade")?;
+    response.headers().insert("sec-websocket-accept", derive_accept_key(key.as_bytes()))?;
+    Ok(response)
+}
+
+/// A data message received from or sent to a WebSocket peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl WebSocketMessage {
+
+    pub fn json(value: &JsonValue) -> Self {
+        WebSocketMessage::Text(serde_json::to_string(value).unwrap())
+    }
+
+    pub fn teon(value: &Value) -> Result<Self> {
+        match JsonValue::try_from(value) {
+            Ok(json_value) => Ok(Self::json(&json_value)),
+            Err(_) => Err(Error::internal_server_error_message("cannot convert teon value to json")),
+        }
+    }
+
+    pub fn as_json(&self) -> Result<JsonValue> {
+        let parsed = match self {
+            WebSocketMessage::Text(text) => serde_json::from_str(text),
+            WebSocketMessage::Binary(binary) => serde_json::from_slice(binary),
+        };
+        parsed.map_err(|_| Error::invalid_request_message("incorrect json format"))
+    }
+
+    pub(super) fn into_message(self) -> Message {
+        match self {
+            WebSocketMessage::Text(text) => Message::Text(text),
+            WebSocketMessage::Binary(binary) => Message::Binary(binary),
+        }
+    }
+
+    /// Control frames are answered by the protocol itself and yield `None`.
+    pub(super) fn from_message(message: Message) -> Option<Self> {
+        match message {
+            Message::Text(text) => Some(WebSocketMessage::Text(text)),
+            Message::Binary(binary) => Some(WebSocketMessage::Binary(binary)),
+            _ => None,
+        }
+    }
+}
+
+/// The server side of an upgraded WebSocket connection.
+pub struct WebSocket {
+    stream: WebSocketStream<TokioIo<Upgraded>>,
+    shutdown: Option<watch::Receiver<bool>>,
+}
+
+impl WebSocket {
+
+    /// Receive the next data message, `None` once the peer closed the connection or the server
+    /// is shutting down, in which case the connection is closed with `1001 Going Away`.
+    pub async fn recv(&mut self) -> Option<Result<WebSocketMessage>> {
+        loop {
+            let message = match self.shutdown.as_mut() {
+                Some(shutdown) => tokio::select! {
+                    message = self.stream.next() => message,
+                    _ = shutdown.wait_for(|shutdown| *shutdown) => None,
+                },
+                None => self.stream.next().await,
+            };
+            let Some(message) = message else {
+                if self.shutdown.as_ref().is_some_and(|shutdown| *shutdown.borrow()) {
+                    let _ = self.stream.close(Some(CloseFrame { code: CloseCode::Away, reason: "server shutting down".into() })).await;
+                }
+                return None;
+            };
+            match message {
+                Ok(Message::Close(_)) => return None,
+                Ok(message) => if let Some(message) = WebSocketMessage::from_message(message) {
+                    return Some(Ok(message));
+                },
+                Err(err) => return Some(Err(Error::new(format!("websocket error: {}", err)))),
+            }
+        }
+    }
+
+    pub async fn send(&mut self, message: WebSocketMessage) -> Result<()> {
+        self.stream.send(message.into_message()).await.map_err(|err| Error::new(format!("websocket error: {}", err)))
+    }
+
+    pub async fn send_json(&mut self, value: &JsonValue) -> Result<()> {
+        self.send(WebSocketMessage::json(value)).await
+    }
+
+    pub async fn send_teon(&mut self, value: &Value) -> Result<()> {
+        self.send(WebSocketMessage::teon(value)?).await
+    }
+
+    pub async fn close(mut self) -> Result<()> {
+        self.stream.close(None).await.map_err(|err| Error::new(format!("websocket error: {}", err)))
+    }
+}
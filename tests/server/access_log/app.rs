@@ -0,0 +1,50 @@
+use std::path::{Path, PathBuf};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::access_log::{AccessLogConfig, AccessLogFormat, AccessLogOutput};
+use teo::server::listener::ListenerConfig;
+use teo::test::schema_path::schema_path_args;
+
+pub fn access_log_path() -> PathBuf {
+    std::env::temp_dir().join("teo-access-log-test.jsonl")
+}
+
+pub fn common_access_log_path() -> PathBuf {
+    std::env::temp_dir().join("teo-access-log-test.log")
+}
+
+pub fn load_app() -> Result<App> {
+    load(AccessLogFormat::JsonLines, access_log_path(), vec![])
+}
+
+/// The same app logging in the common format, served on 4051.
+pub fn load_common_app() -> Result<App> {
+    load(AccessLogFormat::Common, common_access_log_path(), vec![ListenerConfig::Tcp { addr: "127.0.0.1:4051".parse().unwrap() }])
+}
+
+fn load(format: AccessLogFormat, path: PathBuf, listeners: Vec<ListenerConfig>) -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let _ = std::fs::remove_file(&path);
+    let mut config = app.server_config();
+    config.listeners = listeners;
+    config.access_log = Some(AccessLogConfig {
+        format,
+        output: AccessLogOutput::File(path),
+    });
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("hello", |_req: Request| async move {
+        Ok(Response::teon(teon!({
+            "hello": "world"
+        })))
+    });
+    app.main_namespace().define_handler("file", |_req: Request| async move {
+        let path = Path::new(file!());
+        Ok(Response::file(path.parent().unwrap().join("data.txt")))
+    });
+    Ok(app)
+}
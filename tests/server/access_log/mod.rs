@@ -0,0 +1,166 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use hyper_util::rt::TokioIo;
+    use serde_json::Value;
+    use serial_test::serial;
+    use tokio::net::TcpStream;
+    use teo::server::server::Server;
+    use crate::server::access_log::app::{access_log_path, common_access_log_path, load_app, load_common_app};
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut COMMON_SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    fn common_server() -> &'static Server {
+        unsafe { COMMON_SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        tokio::spawn(async {
+            server().serve(false).await.unwrap();
+        });
+        unsafe {
+            COMMON_SERVER.get_or_init(|| {
+                Server::new(load_common_app().unwrap())
+            })
+        };
+        common_server().setup_app_for_unit_test().await.unwrap();
+        tokio::spawn(async {
+            common_server().serve(false).await.unwrap();
+        });
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn get(path: &str) -> u16 {
+        send(4032, path, "").await
+    }
+
+    async fn send(port: u16, path: &str, body: &'static str) -> u16 {
+        let mut stream = None;
+        for _ in 0..50 {
+            if let Ok(connected) = TcpStream::connect(format!("127.0.0.1:{port}")).await {
+                stream = Some(connected);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream.unwrap())).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri(path)
+            .header("host", format!("127.0.0.1:{port}"))
+            .body(Full::new(Bytes::from_static(body.as_bytes())))
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        let status = res.status().as_u16();
+        res.into_body().collect().await.unwrap();
+        status
+    }
+
+    /// The entry of the last request to `path`, lines are written by a background thread.
+    async fn entry_for(path: &str) -> Value {
+        for _ in 0..50 {
+            let content = std::fs::read_to_string(access_log_path()).unwrap_or_default();
+            let entry = content.lines().rev()
+                .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+                .find(|entry| entry["path"] == path);
+            if let Some(entry) = entry {
+                return entry;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("no access log entry for {}", path);
+    }
+
+    /// The common format line of the last request to `path`.
+    async fn common_line_for(path: &str) -> String {
+        let request_line = format!("\"GET {} HTTP/1.1\"", path);
+        for _ in 0..50 {
+            let content = std::fs::read_to_string(common_access_log_path()).unwrap_or_default();
+            if let Some(line) = content.lines().rev().find(|line| line.contains(&request_line)) {
+                return line.to_owned();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("no access log line for {}", path);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn logs_matched_handler() {
+        before_all().await;
+        assert_eq!(get("/hello").await, 200);
+        let entry = entry_for("/hello").await;
+        assert_eq!(entry["method"], "GET");
+        assert_eq!(entry["path"], "/hello");
+        assert_eq!(entry["action"], "hello");
+        assert_eq!(entry["namespace"], serde_json::json!([]));
+        assert_eq!(entry["status"], 200);
+        assert_eq!(entry["clientIp"], "127.0.0.1");
+        assert_eq!(entry["responseSize"], 17);
+        assert!(entry["latencyMs"].as_f64().unwrap() >= 0.0);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn logs_streamed_body_size() {
+        before_all().await;
+        assert_eq!(get("/file").await, 200);
+        let entry = entry_for("/file").await;
+        assert_eq!(entry["status"], 200);
+        assert_eq!(entry["responseSize"], 19);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn logs_errors() {
+        before_all().await;
+        assert_eq!(get("/not-found").await, 404);
+        let entry = entry_for("/not-found").await;
+        assert_eq!(entry["path"], "/not-found");
+        assert_eq!(entry["status"], 404);
+        assert_eq!(entry["action"], Value::Null);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn common_format_logs_request_size() {
+        before_all().await;
+        assert_eq!(send(4051, "/hello", "hello").await, 200);
+        let line = common_line_for("/hello").await;
+        let fields = line.split("HTTP/1.1\" ").nth(1).unwrap().split(' ').collect::<Vec<_>>();
+        assert_eq!(fields[0], "200");
+        assert_eq!(fields[1], "17");
+        assert_eq!(fields[2], "5");
+        assert_eq!(fields[3], "\"hello\"");
+        assert!(line.starts_with("127.0.0.1 - - ["));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn logs_request_size() {
+        before_all().await;
+        assert_eq!(send(4032, "/hello", "hello").await, 200);
+        let entry = entry_for("/hello").await;
+        assert_eq!(entry["requestSize"], 5);
+    }
+}
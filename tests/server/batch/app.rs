@@ -0,0 +1,31 @@
+use std::time::Duration;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::batch::{batch_request_id, BatchConfig};
+use teo::server::request_id::request_id;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.batch = Some(BatchConfig::default());
+    config.timeouts.default = Some(Duration::from_millis(200));
+    config.timeouts.handlers.insert("slow".to_owned(), None);
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("slow", |_req: Request| async move {
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        Ok(Response::teon(teon!({ "finished": true })))
+    });
+    app.main_namespace().define_handler("ids", |req: Request| async move {
+        Ok(Response::teon(teon!({
+            "requestId": request_id(&req).unwrap_or_default(),
+            "batchRequestId": batch_request_id(&req).unwrap_or_default(),
+        })))
+    });
+    Ok(app)
+}
@@ -0,0 +1,120 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::batch::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn count(name: &str) -> u64 {
+        let req = TestRequest::new(Method::POST, "/Item/count").json_body(json!({ "where": { "name": name } })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        res.body_as_json().unwrap()["data"].as_u64().unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn runs_items_in_order_with_references() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/batch").json_body(json!({
+            "items": [
+                { "model": "Item", "action": "create", "args": { "create": { "name": "original" } } },
+                { "model": "Item", "action": "create", "args": { "create": { "name": "copy", "copiedFromId": { "$ref": "0.data.id" } } } },
+            ]
+        })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let data = res.body_as_json().unwrap()["data"].clone();
+        assert_eq!(data.as_array().unwrap().len(), 2);
+        assert_eq!(data[1]["data"]["name"], "copy");
+        assert_eq!(data[1]["data"]["copiedFromId"], data[0]["data"]["id"]);
+        assert!(res.headers().get("x-request-id").unwrap().is_some());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn rolls_back_every_item_when_one_fails() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/batch").json_body(json!({
+            "items": [
+                { "model": "Item", "action": "create", "args": { "create": { "name": "rolled back" } } },
+                { "model": "Item", "action": "create", "args": { "create": {} } },
+            ]
+        })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+        assert!(res.body_as_json().unwrap()["error"]["message"].as_str().unwrap().starts_with("batch item 1 failed"));
+        assert_eq!(count("rolled back").await, 0);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn rejects_oversized_and_nested_batches() {
+        before_all().await;
+        let items: Vec<_> = (0..101).map(|_| json!({ "model": "Item", "action": "count" })).collect();
+        let req = TestRequest::new(Method::POST, "/batch").json_body(json!({ "items": items })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+        for path in ["/batch", "batch"] {
+            let req = TestRequest::new(Method::POST, "/batch").json_body(json!({ "items": [{ "path": path }] })).await.unwrap();
+            let res = server().process_test_request(req).await.unwrap();
+            assert_eq!(res.status().as_u16(), 400);
+        }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn default_timeout_covers_the_batch() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/batch").json_body(json!({ "items": [{ "method": "GET", "path": "/slow" }] })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 504);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn items_get_their_own_request_ids() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/batch")
+            .insert_header("x-request-id", "batch-1").unwrap()
+            .json_body(json!({
+                "items": [
+                    { "method": "GET", "path": "/ids" },
+                    { "method": "GET", "path": "/ids" },
+                ]
+            })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.headers().get("x-request-id").unwrap().unwrap(), "batch-1");
+        let data = res.body_as_json().unwrap()["data"].clone();
+        assert_eq!(data[0]["batchRequestId"], "batch-1");
+        assert_eq!(data[1]["batchRequestId"], "batch-1");
+        assert_ne!(data[0]["requestId"], "batch-1");
+        assert_ne!(data[0]["requestId"], "");
+        assert_ne!(data[0]["requestId"], data[1]["requestId"]);
+    }
+}
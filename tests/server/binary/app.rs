@@ -0,0 +1,9 @@
+use teo::app::App;
+use teo::result::Result;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )
+}
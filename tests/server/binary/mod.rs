@@ -0,0 +1,143 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::io::Cursor;
+    use bytes::Bytes;
+    use ciborium::value::Value as CborValue;
+    use http_body_util::Full;
+    use hyper::Method;
+    use rmpv::Value as MsgPackValue;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::binary::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    fn msgpack_get<'a>(map: &'a MsgPackValue, key: &str) -> &'a MsgPackValue {
+        map.as_map().unwrap().iter().find(|(k, _)| k.as_str() == Some(key)).map(|(_, v)| v).unwrap()
+    }
+
+    fn cbor_get<'a>(map: &'a CborValue, key: &str) -> &'a CborValue {
+        map.as_map().unwrap().iter().find(|(k, _)| k.as_text() == Some(key)).map(|(_, v)| v).unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn msgpack_keeps_tagged_values() {
+        before_all().await;
+        // 2024-05-01T10:00:00Z as a 96 bit timestamp
+        let mut timestamp = 0u32.to_be_bytes().to_vec();
+        timestamp.extend(1714557600i64.to_be_bytes());
+        let body = MsgPackValue::Map(vec![(MsgPackValue::from("create"), MsgPackValue::Map(vec![
+            (MsgPackValue::from("sensor"), MsgPackValue::from("msgpack")),
+            (MsgPackValue::from("value"), MsgPackValue::Ext(1, b"12.5".to_vec())),
+            (MsgPackValue::from("day"), MsgPackValue::Ext(2, b"2024-05-01".to_vec())),
+            (MsgPackValue::from("takenAt"), MsgPackValue::Ext(-1, timestamp.clone())),
+        ]))]);
+        let mut bytes = vec![];
+        rmpv::encode::write_value(&mut bytes, &body).unwrap();
+        let req = TestRequest::new(Method::POST, "/Reading/create")
+            .insert_header("content-type", "application/msgpack").unwrap()
+            .insert_header("accept", "application/msgpack").unwrap()
+            .set_body(Full::new(Bytes::from(bytes))).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.headers().get("content-type").unwrap().unwrap(), "application/msgpack");
+        let value = rmpv::decode::read_value(&mut Cursor::new(res.body().as_ref())).unwrap();
+        let data = msgpack_get(&value, "data");
+        assert_eq!(msgpack_get(data, "sensor").as_str(), Some("msgpack"));
+        let MsgPackValue::Ext(1, decimal) = msgpack_get(data, "value") else { panic!("decimal is not tagged") };
+        assert_eq!(String::from_utf8(decimal.clone()).unwrap().parse::<f64>().unwrap(), 12.5);
+        assert_eq!(msgpack_get(data, "day"), &MsgPackValue::Ext(2, b"2024-05-01".to_vec()));
+        assert_eq!(msgpack_get(data, "takenAt"), &MsgPackValue::Ext(-1, timestamp));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn cbor_keeps_tagged_values() {
+        before_all().await;
+        let body = CborValue::Map(vec![(CborValue::Text("create".to_owned()), CborValue::Map(vec![
+            (CborValue::Text("sensor".to_owned()), CborValue::Text("cbor".to_owned())),
+            (CborValue::Text("value".to_owned()), CborValue::Tag(4, Box::new(CborValue::Array(vec![CborValue::Integer((-2).into()), CborValue::Integer(1250.into())])))),
+            (CborValue::Text("day".to_owned()), CborValue::Tag(1004, Box::new(CborValue::Text("2024-05-01".to_owned())))),
+            (CborValue::Text("takenAt".to_owned()), CborValue::Tag(1, Box::new(CborValue::Integer(1714557600.into())))),
+        ]))]);
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&body, &mut bytes).unwrap();
+        let req = TestRequest::new(Method::POST, "/Reading/create")
+            .insert_header("content-type", "application/cbor").unwrap()
+            .insert_header("accept", "application/json;q=0.5, application/cbor").unwrap()
+            .set_body(Full::new(Bytes::from(bytes))).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.headers().get("content-type").unwrap().unwrap(), "application/cbor");
+        let value: CborValue = ciborium::de::from_reader(Cursor::new(res.body().as_ref())).unwrap();
+        let data = cbor_get(&value, "data");
+        let CborValue::Tag(4, fraction) = cbor_get(data, "value") else { panic!("decimal is not tagged") };
+        let [CborValue::Integer(exponent), CborValue::Integer(mantissa)] = fraction.as_array().unwrap().as_slice() else { panic!("invalid decimal fraction") };
+        assert_eq!(i128::from(*mantissa) as f64 * 10f64.powi(i128::from(*exponent) as i32), 12.5);
+        assert_eq!(cbor_get(data, "day"), &CborValue::Tag(1004, Box::new(CborValue::Text("2024-05-01".to_owned()))));
+        assert_eq!(cbor_get(data, "takenAt"), &CborValue::Tag(0, Box::new(CborValue::Text("2024-05-01T10:00:00.000Z".to_owned()))));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn cbor_rejects_oversized_numbers() {
+        before_all().await;
+        let huge_exponent = CborValue::Tag(4, Box::new(CborValue::Array(vec![CborValue::Integer(1_000_000_000i64.into()), CborValue::Integer(1.into())])));
+        let huge_bignum = CborValue::Tag(2, Box::new(CborValue::Bytes(vec![0xff; 65])));
+        for value in [huge_exponent, huge_bignum] {
+            let body = CborValue::Map(vec![(CborValue::Text("create".to_owned()), CborValue::Map(vec![
+                (CborValue::Text("sensor".to_owned()), CborValue::Text("cbor".to_owned())),
+                (CborValue::Text("value".to_owned()), value),
+            ]))]);
+            let mut bytes = vec![];
+            ciborium::ser::into_writer(&body, &mut bytes).unwrap();
+            let req = TestRequest::new(Method::POST, "/Reading/create")
+                .insert_header("content-type", "application/cbor").unwrap()
+                .set_body(Full::new(Bytes::from(bytes))).await.unwrap();
+            let res = server().process_test_request(req).await.unwrap();
+            assert_eq!(res.status().as_u16(), 400);
+        }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_stays_the_default() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/Reading/findMany")
+            .insert_header("accept", "application/json, application/msgpack;q=0.5").unwrap()
+            .json_body(json!({ "orderBy": { "id": "asc" } })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert!(res.headers().get("content-type").unwrap().unwrap().starts_with("application/json"));
+        assert!(res.body_as_json().unwrap()["data"].is_array());
+        let req = TestRequest::new(Method::POST, "/Reading/create")
+            .insert_header("content-type", "application/msgpack").unwrap()
+            .set_body(Full::new(Bytes::from_static(&[0xc1]))).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+    }
+}
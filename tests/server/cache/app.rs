@@ -0,0 +1,23 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::cache::CachePolicy;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.cache.handlers.insert("Post.findMany".to_owned(), CachePolicy {
+        cache_control: Some("private, max-age=60".to_owned()),
+        last_modified_field: Some("updatedAt".to_owned()),
+    });
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("findMany", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "data": [] })))
+    });
+    Ok(app)
+}
@@ -0,0 +1,91 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::{json, Value};
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use teo::server::test_response::TestResponse;
+    use crate::server::cache::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn post(action: &str, args: Value, headers: &[(&str, &str)]) -> TestResponse {
+        let mut req = TestRequest::new(Method::POST, &format!("/Post/{}", action));
+        for (name, value) in headers {
+            req = req.insert_header(*name, *value).unwrap();
+        }
+        server().process_test_request(req.json_body(args).await.unwrap()).await.unwrap()
+    }
+
+    fn header(res: &TestResponse, name: &str) -> Option<String> {
+        res.headers().get(name).unwrap().map(|value| value.to_string())
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn read_actions_answer_matching_etag_with_not_modified() {
+        before_all().await;
+        let res = post("create", json!({ "create": { "title": "a", "updatedAt": "2024-05-01T10:00:00.000Z" } }), &[]).await;
+        assert!(header(&res, "etag").is_none());
+        let id = res.body_as_json().unwrap()["data"]["id"].clone();
+        let res = post("findUnique", json!({ "where": { "id": id } }), &[]).await;
+        assert_eq!(res.status().as_u16(), 200);
+        let etag = header(&res, "etag").unwrap();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        let res = post("findUnique", json!({ "where": { "id": id } }), &[("if-none-match", etag.as_str())]).await;
+        assert_eq!(res.status().as_u16(), 304);
+        assert!(res.body().is_empty());
+        assert_eq!(header(&res, "etag").unwrap(), etag);
+        post("update", json!({ "where": { "id": id }, "update": { "title": "b" } }), &[]).await;
+        let res = post("findUnique", json!({ "where": { "id": id } }), &[("if-none-match", etag.as_str())]).await;
+        assert_eq!(res.status().as_u16(), 200);
+        assert_ne!(header(&res, "etag").unwrap(), etag);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn handlers_set_last_modified_and_cache_control() {
+        before_all().await;
+        post("create", json!({ "create": { "title": "c", "updatedAt": "2024-06-01T08:30:00.000Z" } }), &[]).await;
+        let res = post("findMany", json!({}), &[]).await;
+        assert_eq!(header(&res, "cache-control").unwrap(), "private, max-age=60");
+        let last_modified = header(&res, "last-modified").unwrap();
+        assert_eq!(last_modified, "Sat, 01 Jun 2024 08:30:00 GMT");
+        let res = post("findMany", json!({}), &[("if-modified-since", last_modified.as_str())]).await;
+        assert_eq!(res.status().as_u16(), 304);
+        let res = post("findMany", json!({}), &[("if-modified-since", "Fri, 31 May 2024 00:00:00 GMT")]).await;
+        assert_eq!(res.status().as_u16(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn custom_handlers_named_like_read_actions_get_no_etag() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/findMany").json_body(json!({})).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert!(header(&res, "etag").is_none());
+    }
+}
@@ -0,0 +1,39 @@
+use std::path::Path;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::{teon, Value};
+use teo::app::App;
+use teo::result::Result;
+use teo::server::compression::CompressionConfig;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.compression = Some(CompressionConfig::default());
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("large", |_req: Request| async move {
+        let items: Vec<Value> = (0..200).map(|i| teon!({ "index": i, "name": "compressible" })).collect();
+        Ok(Response::teon(teon!({
+            "data": items
+        })))
+    });
+    app.main_namespace().define_handler("huge", |_req: Request| async move {
+        let items: Vec<Value> = (0..5000).map(|i| teon!({ "index": i, "name": "compressible" })).collect();
+        Ok(Response::teon(teon!({
+            "data": items
+        })))
+    });
+    app.main_namespace().define_handler("small", |_req: Request| async move {
+        Ok(Response::teon(teon!({
+            "data": "small"
+        })))
+    });
+    app.main_namespace().define_handler("file", |_req: Request| async move {
+        let path = Path::new(file!());
+        Ok(Response::file(path.parent().unwrap().join("data.txt")))
+    });
+    Ok(app)
+}
@@ -0,0 +1,146 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::io::Read;
+    use flate2::read::GzDecoder;
+    use hyper::Method;
+    use serde_json::Value;
+    use serial_test::serial;
+    use teo::server::compression::{CompressionConfig, ContentEncoding};
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::compression::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    #[test]
+    fn negotiate_by_weight_and_preference() {
+        let config = CompressionConfig::default();
+        assert_eq!(config.negotiate("gzip, br"), Some(ContentEncoding::Brotli));
+        assert_eq!(config.negotiate("gzip, br;q=0.5"), Some(ContentEncoding::Gzip));
+        assert_eq!(config.negotiate("zstd, gzip"), Some(ContentEncoding::Zstd));
+        assert_eq!(config.negotiate("br;q=0, *"), Some(ContentEncoding::Zstd));
+        assert_eq!(config.negotiate("identity"), None);
+        assert_eq!(config.negotiate(""), None);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn gzip_json_response() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/large")
+            .insert_header("accept-encoding", "gzip").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.headers().get("content-encoding").unwrap().unwrap(), "gzip");
+        assert_eq!(res.headers().get("vary").unwrap().unwrap(), "accept-encoding");
+        let mut decoded = String::new();
+        GzDecoder::new(res.body().as_ref()).read_to_string(&mut decoded).unwrap();
+        let json: Value = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(json["data"].as_array().unwrap().len(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn gzip_response_over_blocking_size() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/huge")
+            .insert_header("accept-encoding", "gzip").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.headers().get("content-encoding").unwrap().unwrap(), "gzip");
+        let mut decoded = String::new();
+        GzDecoder::new(res.body().as_ref()).read_to_string(&mut decoded).unwrap();
+        let json: Value = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(json["data"].as_array().unwrap().len(), 5000);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn brotli_json_response() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/large")
+            .insert_header("accept-encoding", "gzip, deflate, br").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.headers().get("content-encoding").unwrap().unwrap(), "br");
+        let mut decoded = String::new();
+        brotli::Decompressor::new(res.body().as_ref(), 4096).read_to_string(&mut decoded).unwrap();
+        let json: Value = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(json["data"].as_array().unwrap().len(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn zstd_json_response() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/large")
+            .insert_header("accept-encoding", "zstd").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.headers().get("content-encoding").unwrap().unwrap(), "zstd");
+        let decoded = zstd::decode_all(res.body().as_ref()).unwrap();
+        let json: Value = serde_json::from_slice(&decoded).unwrap();
+        assert_eq!(json["data"].as_array().unwrap().len(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn no_compression_without_accept_encoding() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/large");
+        let res = server().process_test_request(req).await.unwrap();
+        assert!(res.headers().get("content-encoding").unwrap().is_none());
+        assert_eq!(res.headers().get("vary").unwrap().unwrap(), "accept-encoding");
+        assert_eq!(res.body_as_json().unwrap()["data"].as_array().unwrap().len(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn no_compression_below_min_size() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/small")
+            .insert_header("accept-encoding", "gzip").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert!(res.headers().get("content-encoding").unwrap().is_none());
+        assert_eq!(res.body_as_json().unwrap()["data"], "small");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn precompressed_file_sibling() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/file")
+            .insert_header("accept-encoding", "gzip").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.headers().get("content-encoding").unwrap().unwrap(), "gzip");
+        let mut decoded = String::new();
+        GzDecoder::new(res.body().as_ref()).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, "gzipped file");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn file_without_accept_encoding() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/file");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.body_as_string(), "plain file");
+    }
+}
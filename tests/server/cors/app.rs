@@ -0,0 +1,48 @@
+use std::time::Duration;
+use regex::Regex;
+use teo_runtime::arguments::Arguments;
+use teo_runtime::middleware::next::{Next, NextImp};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::prelude::Error;
+use teo::result::Result;
+use teo::server::cors::{CorsConfig, CorsOrigin};
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.cors = Some(CorsConfig {
+        origins: vec![
+            CorsOrigin::Exact("https://app.example.com".to_owned()),
+            CorsOrigin::Regex(Regex::new(r"https://[a-z]+\.preview\.example\.com").unwrap()),
+        ],
+        methods: None,
+        headers: vec!["authorization".to_owned(), "content-type".to_owned()],
+        expose_headers: vec!["x-request-id".to_owned()],
+        credentials: true,
+        max_age: Some(Duration::from_secs(300)),
+    });
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("listItems", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "items": [] })))
+    });
+    app.main_namespace().define_handler("createItem", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "created": true })))
+    });
+    app.main_namespace().define_handler_middleware("auth", |_arguments: Arguments| {
+        Ok(|req: Request, next: Next| async move {
+            if req.headers().get("authorization")?.is_none() {
+                let mut error = Error::new("unauthorized");
+                error.code = 401;
+                return Err(error);
+            }
+            Ok(next.call(req).await?)
+        })
+    });
+    Ok(app)
+}
@@ -0,0 +1,126 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serial_test::serial;
+    use teo::server::cors::{CorsConfig, CorsOrigin};
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::cors::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    fn preflight(path: &str, origin: &str) -> TestRequest {
+        TestRequest::new(Method::OPTIONS, path)
+            .insert_header("origin", origin).unwrap()
+            .insert_header("access-control-request-method", "POST").unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn preflight_lists_registered_methods() {
+        before_all().await;
+        let res = server().process_test_request(preflight("/items", "https://app.example.com")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 204);
+        assert_eq!(res.headers().get("access-control-allow-methods").unwrap().unwrap(), "GET, POST");
+        assert_eq!(res.headers().get("access-control-allow-origin").unwrap().unwrap(), "https://app.example.com");
+        assert_eq!(res.headers().get("access-control-allow-credentials").unwrap().unwrap(), "true");
+        assert_eq!(res.headers().get("access-control-allow-headers").unwrap().unwrap(), "authorization, content-type");
+        assert_eq!(res.headers().get("access-control-max-age").unwrap().unwrap(), "300");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn preflight_for_unknown_path() {
+        before_all().await;
+        let res = server().process_test_request(preflight("/unknown", "https://app.example.com")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn regex_origin() {
+        before_all().await;
+        let res = server().process_test_request(preflight("/items", "https://feature.preview.example.com")).await.unwrap();
+        assert_eq!(res.headers().get("access-control-allow-origin").unwrap().unwrap(), "https://feature.preview.example.com");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn disallowed_origin() {
+        before_all().await;
+        let res = server().process_test_request(preflight("/items", "https://evil.example.org")).await.unwrap();
+        assert!(res.headers().get("access-control-allow-origin").unwrap().is_none());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn actual_request_headers() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/items")
+            .insert_header("origin", "https://app.example.com").unwrap()
+            .insert_header("authorization", "Bearer token").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.headers().get("access-control-allow-origin").unwrap().unwrap(), "https://app.example.com");
+        assert_eq!(res.headers().get("access-control-expose-headers").unwrap().unwrap(), "x-request-id");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn error_response_headers() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/items").insert_header("origin", "https://app.example.com").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 401);
+        assert_eq!(res.headers().get("access-control-allow-origin").unwrap().unwrap(), "https://app.example.com");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn regex_matches_whole_origin() {
+        before_all().await;
+        let res = server().process_test_request(preflight("/items", "https://feature.preview.example.com.evil.io")).await.unwrap();
+        assert!(res.headers().get("access-control-allow-origin").unwrap().is_none());
+        assert!(res.headers().get("access-control-allow-credentials").unwrap().is_none());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn varies_by_origin_without_origin_header() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/items").insert_header("authorization", "Bearer token").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.headers().get("vary").unwrap().unwrap(), "origin");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn any_origin_with_credentials_is_rejected() {
+        before_all().await;
+        let mut config = server().app.server_config();
+        config.cors = Some(CorsConfig { origins: vec![CorsOrigin::Any], credentials: true, ..CorsConfig::default() });
+        assert!(server().app.replace_server_config(config).is_err());
+        assert_eq!(server().config().cors.as_ref().unwrap().origins.len(), 2);
+    }
+}
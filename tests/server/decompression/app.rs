@@ -0,0 +1,21 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo::app::App;
+use teo::result::Result;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.limits.default.json_body_size = 1024;
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("echo", |req: Request| async move {
+        Ok(Response::teon(req.body_value()?.clone()))
+    });
+    app.main_namespace().define_handler("upload", |req: Request| async move {
+        Ok(Response::teon(req.body_value()?.clone()))
+    });
+    Ok(app)
+}
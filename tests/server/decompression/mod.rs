@@ -0,0 +1,134 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::io::Write;
+    use flate2::Compression;
+    use form_data_builder::FormData;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use hyper::Method;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::{assert_json, matcher};
+    use crate::server::decompression::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+        encoder.into_inner()
+    }
+
+    async fn encoded_request(encoding: &str, body: Vec<u8>) -> TestRequest {
+        TestRequest::new(Method::POST, "/echo")
+            .insert_header("content-type", "application/json").unwrap()
+            .insert_header("content-encoding", encoding).unwrap()
+            .set_body(http_body_util::Full::new(bytes::Bytes::from(body))).await.unwrap()
+    }
+
+    fn payload() -> Vec<u8> {
+        serde_json::to_vec(&json!({ "name": "compressed" })).unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn gzip_body() {
+        before_all().await;
+        let res = server().process_test_request(encoded_request("gzip", gzip(&payload())).await).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "name": "compressed" }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn deflate_body() {
+        before_all().await;
+        let res = server().process_test_request(encoded_request("deflate", deflate(&payload())).await).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "name": "compressed" }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn brotli_body() {
+        before_all().await;
+        let res = server().process_test_request(encoded_request("br", brotli(&payload())).await).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "name": "compressed" }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn decompressed_size_is_limited() {
+        before_all().await;
+        let bomb = serde_json::to_vec(&json!({ "name": "0".repeat(1024 * 1024) })).unwrap();
+        let compressed = gzip(&bomb);
+        assert!(compressed.len() < 1024);
+        let res = server().process_test_request(encoded_request("gzip", compressed).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn malformed_body() {
+        before_all().await;
+        let mut corrupted = gzip(&payload());
+        corrupted.truncate(corrupted.len() / 2);
+        let res = server().process_test_request(encoded_request("gzip", corrupted).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn gzip_multipart_body() {
+        before_all().await;
+        let mut form = FormData::new(Vec::new());
+        form.write_field("name", "compressed").unwrap();
+        let content_type = form.content_type_header();
+        let req = TestRequest::new(Method::POST, "/upload")
+            .insert_header("content-type", content_type).unwrap()
+            .insert_header("content-encoding", "gzip").unwrap()
+            .set_body(http_body_util::Full::new(bytes::Bytes::from(gzip(&form.finish().unwrap())))).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "name": "compressed" }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn unsupported_encoding() {
+        before_all().await;
+        let res = server().process_test_request(encoded_request("compress", payload()).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 415);
+    }
+}
@@ -0,0 +1,25 @@
+use teo_runtime::arguments::Arguments;
+use teo_runtime::middleware::next::{Next, NextImp};
+use teo_runtime::request::Request;
+use teo::app::App;
+use teo::prelude::Error;
+use teo::result::Result;
+use teo::server::health::HealthConfig;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.health = Some(HealthConfig::default());
+    app.replace_server_config(config)?;
+    app.main_namespace().define_request_middleware("deny", |_arguments: Arguments| {
+        Ok(|_req: Request, _next: Next| async move {
+            let mut error = Error::new("denied");
+            error.code = 403;
+            Err(error)
+        })
+    });
+    Ok(app)
+}
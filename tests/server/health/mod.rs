@@ -0,0 +1,83 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::health::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn liveness_bypasses_middlewares() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/healthz")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.body_as_json().unwrap()["status"], "ok");
+        assert!(res.headers().get("x-request-id").unwrap().is_some());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn readiness_reports_namespaces() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/readyz")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let body = res.body_as_json().unwrap();
+        assert_eq!(body["ready"], true);
+        assert_eq!(body["namespaces"]["main"]["status"], "ok");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn not_ready_before_startup_finished() {
+        before_all().await;
+        server().app.set_ready(false);
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/readyz")).await.unwrap();
+        server().app.set_ready(true);
+        assert_eq!(res.status().as_u16(), 503);
+        let body = res.body_as_json().unwrap();
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["started"], false);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn other_requests_pass_middlewares() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::POST, "/Item/findMany")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 403);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn other_requests_refused_before_startup_finished() {
+        before_all().await;
+        server().app.set_ready(false);
+        let res = server().process_test_request(TestRequest::new(Method::POST, "/Item/findMany")).await.unwrap();
+        server().app.set_ready(true);
+        assert_eq!(res.status().as_u16(), 503);
+    }
+}
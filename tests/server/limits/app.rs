@@ -0,0 +1,38 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::limits::BodyLimits;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.limits.default = BodyLimits {
+        json_body_size: 64,
+        json_depth: 3,
+        file_size: 16,
+        multipart_size: 4096,
+        file_count: 2,
+    };
+    config.limits.handlers.insert("echoLarge".to_owned(), BodyLimits {
+        json_body_size: 4096,
+        ..config.limits.default.clone()
+    });
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("echo", |req: Request| async move {
+        Ok(Response::teon(req.body_value()?.clone()))
+    });
+    app.main_namespace().define_handler("echoLarge", |req: Request| async move {
+        Ok(Response::teon(req.body_value()?.clone()))
+    });
+    app.main_namespace().define_handler("upload", |_req: Request| async move {
+        Ok(Response::teon(teon!({
+            "uploaded": true
+        })))
+    });
+    Ok(app)
+}
@@ -0,0 +1,187 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::ffi::OsStr;
+    use std::path::PathBuf;
+    use bytes::Bytes;
+    use form_data_builder::FormData;
+    use http_body_util::Full;
+    use hyper::Method;
+    use serde_json::{json, Value};
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::{assert_json, matcher};
+    use crate::server::limits::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    fn upload_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    async fn upload_request(form: FormData<Vec<u8>>) -> TestRequest {
+        let header_value = form.content_type_header();
+        let body = form.finish().unwrap();
+        TestRequest::new(Method::POST, "/upload")
+            .insert_header("content-type", header_value).unwrap()
+            .set_body(Full::new(Bytes::from(body))).await.unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_body_within_limit() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/echo").json_body(json!({ "a": 1 })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "a": 1 }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_body_over_limit() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/echo").json_body(json!({ "a": "x".repeat(100) })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "error": {
+                "type": ignore,
+                "message": "request body exceeds the limit of 64 bytes",
+            }
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_body_limit_overridden_by_handler() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/echoLarge").json_body(json!({ "a": "x".repeat(100) })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_depth_over_limit() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/echo").json_body(json!({ "a": { "b": { "c": {} } } })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_depth_over_parser_recursion_limit() {
+        before_all().await;
+        let body = format!("{{\"a\":{}{}}}", "[".repeat(200), "]".repeat(200));
+        let req = TestRequest::new(Method::POST, "/echoLarge")
+            .insert_header("content-type", "application/json").unwrap()
+            .set_body(Full::new(Bytes::from(body))).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "error": {
+                "type": ignore,
+                "message": "request body exceeds the nesting depth limit of 3",
+            }
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn file_within_limit() {
+        before_all().await;
+        let mut form = FormData::new(Vec::new());
+        form.write_path("file", upload_file("teo-limit-small.txt", "small"), "text/plain").unwrap();
+        let res = server().process_test_request(upload_request(form).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn file_over_limit() {
+        before_all().await;
+        let mut form = FormData::new(Vec::new());
+        form.write_path("file", upload_file("teo-limit-large.txt", &"x".repeat(64)), "text/plain").unwrap();
+        let res = server().process_test_request(upload_request(form).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn rejected_upload_removes_earlier_files() {
+        before_all().await;
+        let source_dir = std::env::temp_dir().join("teo-limit-sources");
+        std::fs::create_dir_all(&source_dir).unwrap();
+        let small = source_dir.join("teo-limit-earlier.txt");
+        std::fs::write(&small, "small").unwrap();
+        let large = source_dir.join("teo-limit-later.txt");
+        std::fs::write(&large, "x".repeat(64)).unwrap();
+        let mut form = FormData::new(Vec::new());
+        form.write_path("first", &small, "text/plain").unwrap();
+        form.write_path("second", &large, "text/plain").unwrap();
+        let res = server().process_test_request(upload_request(form).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+        assert!(!std::env::temp_dir().join("teo-limit-earlier.txt").exists());
+        assert!(!std::env::temp_dir().join("teo-limit-later.txt").exists());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn client_file_names_do_not_touch_existing_files() {
+        before_all().await;
+        let existing = std::env::temp_dir().join("teo-limit-existing.txt");
+        std::fs::write(&existing, "keep").unwrap();
+        let mut form = FormData::new(Vec::new());
+        form.write_file("file", "x".repeat(64).as_bytes(), Some(OsStr::new("teo-limit-existing.txt")), "text/plain").unwrap();
+        let res = server().process_test_request(upload_request(form).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "keep");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn file_count_over_limit() {
+        before_all().await;
+        let mut form = FormData::new(Vec::new());
+        for i in 0..3 {
+            form.write_path("files[]", upload_file(&format!("teo-limit-{i}.txt"), "small"), "text/plain").unwrap();
+        }
+        let res = server().process_test_request(upload_request(form).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn multipart_over_limit() {
+        before_all().await;
+        let mut form = FormData::new(Vec::new());
+        form.write_field("text", &"x".repeat(8192)).unwrap();
+        let res = server().process_test_request(upload_request(form).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+    }
+}
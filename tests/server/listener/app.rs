@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::listener::ListenerConfig;
+use teo::test::schema_path::schema_path_args;
+
+pub fn socket_path() -> PathBuf {
+    std::env::temp_dir().join("teo-listener-test.sock")
+}
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.listeners = vec![
+        ListenerConfig::Tcp { addr: "127.0.0.1:4044".parse().unwrap() },
+        ListenerConfig::Tcp { addr: "127.0.0.1:4045".parse().unwrap() },
+        ListenerConfig::Unix { path: socket_path(), mode: Some(0o600) },
+    ];
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("hello", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "hello": true })))
+    });
+    Ok(app)
+}
@@ -0,0 +1,92 @@
+#[cfg(unix)]
+pub mod app;
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Empty};
+    use hyper_util::rt::TokioIo;
+    use serde_json::{json, Value};
+    use serial_test::serial;
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::net::{TcpStream, UnixStream};
+    use teo::server::server::Server;
+    use crate::server::listener::app::{load_app, socket_path};
+
+    async fn hello<S>(stream: S) -> Value where S: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri("/hello")
+            .header("host", "localhost")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn connect_tcp(addr: &str) -> TcpStream {
+        for _ in 0..50 {
+            if let Ok(stream) = TcpStream::connect(addr).await {
+                return stream;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("server is not listening on {}", addr);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn serves_every_listener() {
+        let server = Server::new(load_app().unwrap());
+        server.setup_app_for_unit_test().await.unwrap();
+        let handle = server.shutdown_handle();
+        let serving = {
+            let server = server.clone();
+            tokio::spawn(async move { server.serve(true).await })
+        };
+        assert_eq!(hello(connect_tcp("127.0.0.1:4044").await).await, json!({ "hello": true }));
+        assert_eq!(hello(connect_tcp("127.0.0.1:4045").await).await, json!({ "hello": true }));
+        let mode = std::fs::metadata(socket_path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(hello(UnixStream::connect(socket_path()).await.unwrap()).await, json!({ "hello": true }));
+        handle.shutdown();
+        serving.await.unwrap().unwrap();
+        assert!(!socket_path().exists());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn replaces_stale_socket_file() {
+        drop(std::os::unix::net::UnixListener::bind(socket_path()).unwrap());
+        assert!(socket_path().exists());
+        let server = Server::new(load_app().unwrap());
+        server.setup_app_for_unit_test().await.unwrap();
+        let handle = server.shutdown_handle();
+        let serving = {
+            let server = server.clone();
+            tokio::spawn(async move { server.serve(true).await })
+        };
+        connect_tcp("127.0.0.1:4044").await;
+        assert_eq!(hello(UnixStream::connect(socket_path()).await.unwrap()).await, json!({ "hello": true }));
+        handle.shutdown();
+        serving.await.unwrap().unwrap();
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn keeps_socket_in_use() {
+        let listening = std::os::unix::net::UnixListener::bind(socket_path()).unwrap();
+        let server = Server::new(load_app().unwrap());
+        server.setup_app_for_unit_test().await.unwrap();
+        let err = server.serve(true).await.unwrap_err();
+        assert!(err.message().contains("another server is listening"));
+        assert!(socket_path().exists());
+        drop(listening);
+        std::fs::remove_file(socket_path()).unwrap();
+    }
+}
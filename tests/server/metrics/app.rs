@@ -0,0 +1,14 @@
+use teo::app::App;
+use teo::result::Result;
+use teo::server::metrics::MetricsConfig;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.metrics = Some(MetricsConfig::default());
+    app.replace_server_config(config)?;
+    Ok(app)
+}
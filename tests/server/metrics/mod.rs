@@ -0,0 +1,62 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::metrics::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn scrape() -> String {
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/metrics")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert!(res.headers().get("content-type").unwrap().unwrap().starts_with("text/plain"));
+        res.body_as_string()
+    }
+
+    fn metric_line<'a>(text: &'a str, name: &str, labels: &[&str]) -> Option<&'a str> {
+        text.lines().find(|line| {
+            line.starts_with(&format!("{}{{", name)) && labels.iter().all(|label| line.contains(label))
+        })
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn counts_requests_by_action_and_status() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/Item/create").json_body(json!({ "create": { "name": "a" } })).await.unwrap();
+        assert_eq!(server().process_test_request(req).await.unwrap().status().as_u16(), 200);
+        let req = TestRequest::new(Method::POST, "/Item/create").json_body(json!({ "create": {} })).await.unwrap();
+        assert_eq!(server().process_test_request(req).await.unwrap().status().as_u16(), 400);
+        let text = scrape().await;
+        let created = metric_line(&text, "teo_http_requests_total", &["namespace=\"main\"", "model=\"Item\"", "action=\"create\"", "status=\"200\""]).unwrap();
+        assert!(created.ends_with(" 1"));
+        assert!(metric_line(&text, "teo_http_requests_total", &["action=\"create\"", "status=\"400\""]).is_some());
+        assert!(metric_line(&text, "teo_http_request_duration_seconds_count", &["action=\"create\""]).is_some());
+        assert!(metric_line(&text, "teo_http_request_body_bytes_total", &["action=\"create\""]).is_some());
+        assert!(text.lines().any(|line| line.starts_with("teo_http_requests_in_flight ")));
+    }
+}
@@ -1 +1,26 @@
 pub mod actions;
+pub mod protocol;
+pub mod tls;
+pub mod shutdown;
+pub mod limits;
+pub mod compression;
+pub mod decompression;
+pub mod urlencoded;
+pub mod query;
+pub mod sse;
+pub mod websocket;
+pub mod access_log;
+pub mod request_id;
+pub mod timeout;
+pub mod cors;
+pub mod rate_limit;
+pub mod health;
+pub mod metrics;
+pub mod trace;
+pub mod openapi;
+pub mod batch;
+pub mod transaction;
+pub mod listener;
+pub mod systemd;
+pub mod cache;
+pub mod binary;
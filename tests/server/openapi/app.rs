@@ -0,0 +1,23 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::openapi::OpenApiConfig;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.openapi = Some(OpenApiConfig::default());
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("echo", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "echo": true })))
+    });
+    app.main_namespace().define_handler("upload", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "uploaded": true })))
+    });
+    Ok(app)
+}
@@ -0,0 +1,85 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::Value;
+    use serial_test::serial;
+    use teo::openapi::openapi_document;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::openapi::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn document() -> Value {
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/openapi.json")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        res.body_as_json().unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn serves_generated_document() {
+        before_all().await;
+        let document = document().await;
+        assert_eq!(document["openapi"], "3.1.0");
+        assert_eq!(document, openapi_document(server().app.compiled_main_namespace()));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn describes_model_actions() {
+        before_all().await;
+        let document = document().await;
+        let item = &document["components"]["schemas"]["Item"];
+        assert_eq!(item["properties"]["name"]["type"], "string");
+        assert!(item["required"].as_array().unwrap().contains(&Value::from("name")));
+        assert!(!item["required"].as_array().unwrap().contains(&Value::from("note")));
+        let find_many = &document["paths"]["/Item/findMany"]["post"];
+        assert_eq!(find_many["operationId"], "Item.findMany");
+        assert_eq!(find_many["responses"]["200"]["content"]["application/json"]["schema"]["properties"]["data"]["items"]["$ref"], "#/components/schemas/Item");
+        assert_eq!(find_many["responses"]["default"]["$ref"], "#/components/responses/Error");
+        let arguments = &find_many["requestBody"]["content"]["application/json"]["schema"]["properties"];
+        assert_eq!(arguments["where"]["$ref"], "#/components/schemas/ItemWhereInput");
+        let where_input = &document["components"]["schemas"]["ItemWhereInput"];
+        assert_eq!(where_input["properties"]["name"]["anyOf"][0]["type"], "string");
+        assert_eq!(where_input["properties"]["AND"]["items"]["$ref"], "#/components/schemas/ItemWhereInput");
+        let create = &document["paths"]["/Item/create"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(create["properties"]["create"]["$ref"], "#/components/schemas/ItemCreateInput");
+        assert_eq!(document["components"]["schemas"]["ItemOrderByInput"]["properties"]["id"]["enum"][0], "asc");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn describes_custom_handlers() {
+        before_all().await;
+        let document = document().await;
+        let echo = &document["paths"]["/echo"]["post"];
+        assert_eq!(echo["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/EchoInput");
+        assert_eq!(document["components"]["schemas"]["EchoInput"]["properties"]["message"]["type"], "string");
+        assert!(document["components"]["schemas"]["Error"]["properties"]["error"].is_object());
+        let upload = &document["paths"]["/upload"]["post"]["requestBody"]["content"];
+        assert!(upload["multipart/form-data"].is_object());
+        assert!(upload["application/x-www-form-urlencoded"].is_object());
+    }
+}
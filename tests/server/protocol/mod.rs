@@ -0,0 +1,84 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Empty};
+    use hyper::Version;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use serde_json::{json, Value};
+    use serial_test::serial;
+    use tokio::net::TcpStream;
+    use teo::server::server::Server;
+    use crate::server::protocol::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        tokio::spawn(async {
+            server().serve(true).await.unwrap();
+        });
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn connect() -> TcpStream {
+        for _ in 0..50 {
+            if let Ok(stream) = TcpStream::connect("127.0.0.1:4021").await {
+                return stream;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("server is not listening on 127.0.0.1:4021");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn h2c_with_prior_knowledge() {
+        before_all().await;
+        let stream = connect().await;
+        let (mut sender, connection) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri("http://127.0.0.1:4021/hello")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.version(), Version::HTTP_2);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, json!({ "hello": "world" }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn http11_on_the_same_listener() {
+        before_all().await;
+        let stream = connect().await;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri("/hello")
+            .header("host", "127.0.0.1:4021")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.version(), Version::HTTP_11);
+        assert_eq!(res.status().as_u16(), 200);
+    }
+}
@@ -0,0 +1,19 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    app.main_namespace().define_handler("search", |req: Request| async move {
+        Ok(Response::teon(teon!({ "input": req.body_value()?.clone() })))
+    });
+    app.main_namespace().define_handler("remove", |req: Request| async move {
+        Ok(Response::teon(teon!({ "input": req.body_value()?.clone() })))
+    });
+    Ok(app)
+}
@@ -0,0 +1,150 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::{assert_json, matcher};
+    use crate::server::query::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn without_query() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/search")).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "input": null }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn nested_brackets() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/search?where[name][contains]=Jo&orderBy[][age]=desc");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "input": {
+                "where": { "name": { "contains": "Jo" } },
+                "orderBy": [{ "age": "desc" }],
+            }
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn repeated_keys() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/search?tag=a&tag=b&tag=c&single=d");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "input": {
+                "tag": ["a", "b", "c"],
+                "single": "d",
+            }
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn typed_values() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/search?take=10&exact=true&deleted=null&name=%2210%22&title=hello");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "input": {
+                "take": 10,
+                "exact": true,
+                "deleted": null,
+                "name": "10",
+                "title": "hello",
+            }
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn nesting_depth_over_limit() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, &format!("/search?a{}=1", "[b]".repeat(100)));
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+        let req = TestRequest::new(Method::GET, &format!("/search?q={}{}", "%5B".repeat(100), "%5D".repeat(100)));
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn builtin_find_many_with_query() {
+        before_all().await;
+        for title in ["first", "second", "third"] {
+            let req = TestRequest::new(Method::POST, "/Post/create").json_body(json!({ "create": { "title": title } })).await.unwrap();
+            assert_eq!(server().process_test_request(req).await.unwrap().status().as_u16(), 200);
+        }
+        let req = TestRequest::new(Method::GET, "/Post/findMany?take=2&skip=1&orderBy[id]=asc");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "meta": ignore,
+            "data": [
+                { "id": ignore, "title": "second" },
+                { "id": ignore, "title": "third" },
+            ]
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_q_parameter() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/search?q=%7B%22take%22%3A10%2C%22skip%22%3A5%7D&skip=2");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "input": {
+                "take": 10,
+                "skip": 2,
+            }
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn invalid_q_parameter() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/search?q=%5B1%5D");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn delete_with_query() {
+        before_all().await;
+        let req = TestRequest::new(Method::DELETE, "/remove?id=5");
+        let res = server().process_test_request(req).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "input": { "id": 5 } }));
+    }
+}
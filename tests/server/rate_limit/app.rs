@@ -0,0 +1,26 @@
+use std::time::Duration;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::rate_limit::{RateLimit, RateLimitConfig, RateLimitKey};
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    let mut rate_limit = RateLimitConfig::default();
+    rate_limit.handlers.insert("signIn".to_owned(), RateLimit::new(2, Duration::from_secs(60), RateLimitKey::Header("x-api-key".to_owned())));
+    config.rate_limit = Some(rate_limit);
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("signIn", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "signedIn": true })))
+    });
+    app.main_namespace().define_handler("open", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "open": true })))
+    });
+    Ok(app)
+}
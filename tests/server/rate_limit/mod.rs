@@ -0,0 +1,75 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use teo::server::test_response::TestResponse;
+    use crate::server::rate_limit::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn sign_in(api_key: &str) -> TestResponse {
+        let req = TestRequest::new(Method::POST, "/signIn")
+            .insert_header("x-api-key", api_key).unwrap()
+            .json_body(json!({})).await.unwrap();
+        server().process_test_request(req).await.unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn limited_after_burst() {
+        before_all().await;
+        let first = sign_in("key-a").await;
+        assert_eq!(first.status().as_u16(), 200);
+        assert_eq!(first.headers().get("ratelimit-limit").unwrap().unwrap(), "2");
+        assert_eq!(first.headers().get("ratelimit-remaining").unwrap().unwrap(), "1");
+        let second = sign_in("key-a").await;
+        assert_eq!(second.status().as_u16(), 200);
+        assert_eq!(second.headers().get("ratelimit-remaining").unwrap().unwrap(), "0");
+        let third = sign_in("key-a").await;
+        assert_eq!(third.status().as_u16(), 429);
+        assert_eq!(third.headers().get("retry-after").unwrap().unwrap(), "30");
+        assert_eq!(third.body_as_json().unwrap()["error"]["message"], "too many requests, retry after 30 seconds");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn separate_buckets_per_key() {
+        before_all().await;
+        assert_eq!(sign_in("key-b").await.status().as_u16(), 200);
+        assert_eq!(sign_in("key-b").await.status().as_u16(), 200);
+        assert_eq!(sign_in("key-c").await.status().as_u16(), 200);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn unlimited_handler() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/open")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert!(res.headers().get("ratelimit-limit").unwrap().is_none());
+    }
+}
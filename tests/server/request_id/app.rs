@@ -0,0 +1,23 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::prelude::Error;
+use teo::result::Result;
+use teo::server::request_id::request_id;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    app.main_namespace().define_handler("inspect", |req: Request| async move {
+        Ok(Response::teon(teon!({
+            "requestId": request_id(&req),
+        })))
+    });
+    app.main_namespace().define_handler("fail", |_req: Request| async move {
+        Err::<Response, Error>(Error::invalid_request_message("always fails"))
+    });
+    Ok(app)
+}
@@ -0,0 +1,80 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::request_id::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn generated_id() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/inspect")).await.unwrap();
+        let header = res.headers().get("x-request-id").unwrap().unwrap();
+        assert_eq!(header.len(), 36);
+        assert_eq!(res.body_as_json().unwrap()["requestId"], header.to_string());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn incoming_id() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/inspect").insert_header("x-request-id", "abc-123").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.headers().get("x-request-id").unwrap().unwrap(), "abc-123");
+        assert_eq!(res.body_as_json().unwrap()["requestId"], "abc-123");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn invalid_incoming_id_is_replaced() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/inspect").insert_header("x-request-id", "bad id with spaces").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_ne!(res.headers().get("x-request-id").unwrap().unwrap(), "bad id with spaces");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn id_in_error_response() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/fail").insert_header("x-request-id", "failing-request").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+        assert_eq!(res.headers().get("x-request-id").unwrap().unwrap(), "failing-request");
+        assert_eq!(res.body_as_json().unwrap()["error"]["requestId"], "failing-request");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn id_in_not_found_response() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/missing")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+        assert!(res.body_as_json().unwrap()["error"]["requestId"].is_string());
+    }
+}
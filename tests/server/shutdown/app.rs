@@ -0,0 +1,35 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use teo_runtime::connection::transaction;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::{Error, Result};
+use teo::test::schema_path::schema_path_args;
+
+pub static SHUTDOWN_HOOK_CALLED: AtomicBool = AtomicBool::new(false);
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.shutdown.drain_timeout = Duration::from_secs(5);
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("slow", |_req: Request| async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        Ok(Response::teon(teon!({
+            "finished": true
+        })))
+    });
+    // a failing hook doesn't keep the following ones from running
+    app.on_shutdown(|_ctx: transaction::Ctx| async move {
+        Err(Error::new("cannot release resource"))
+    });
+    app.on_shutdown(|_ctx: transaction::Ctx| async move {
+        SHUTDOWN_HOOK_CALLED.store(true, Ordering::SeqCst);
+        Ok(())
+    });
+    Ok(app)
+}
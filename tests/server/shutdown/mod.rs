@@ -0,0 +1,57 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Empty};
+    use hyper_util::rt::TokioIo;
+    use serde_json::{json, Value};
+    use serial_test::serial;
+    use tokio::net::TcpStream;
+    use teo::server::server::Server;
+    use crate::server::shutdown::app::{load_app, SHUTDOWN_HOOK_CALLED};
+
+    async fn connect() -> TcpStream {
+        for _ in 0..50 {
+            if let Ok(stream) = TcpStream::connect("127.0.0.1:4023").await {
+                return stream;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("server is not listening on 127.0.0.1:4023");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn drains_in_flight_requests_then_runs_hooks() {
+        let server = Server::new(load_app().unwrap());
+        server.setup_app_for_unit_test().await.unwrap();
+        let handle = server.shutdown_handle();
+        let serving = {
+            let server = server.clone();
+            tokio::spawn(async move { server.serve(true).await })
+        };
+        let stream = connect().await;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri("/slow")
+            .header("host", "127.0.0.1:4023")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let in_flight = tokio::spawn(async move { sender.send_request(req).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.shutdown();
+        let res = in_flight.await.unwrap().unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, json!({ "finished": true }));
+        serving.await.unwrap().unwrap();
+        assert!(handle.is_shutdown());
+        assert!(SHUTDOWN_HOOK_CALLED.load(Ordering::SeqCst));
+        assert!(TcpStream::connect("127.0.0.1:4023").await.is_err());
+    }
+}
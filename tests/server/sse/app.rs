@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use teo_runtime::arguments::Arguments;
+use teo_runtime::middleware::next::{Next, NextImp};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::sse::{EventStream, SseEvent};
+use teo::test::schema_path::schema_path_args;
+
+pub static TICKER_DISCONNECTED: AtomicBool = AtomicBool::new(false);
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    app.main_namespace().define_handler("events", |req: Request| async move {
+        let events = vec![
+            SseEvent::new(teon!({ "n": 1 })).event("created").id("1").retry(Duration::from_secs(3)),
+            SseEvent::new(teon!({ "n": 2 })).event("updated").id("2"),
+            SseEvent::new(teon!({ "n": 3 })),
+        ];
+        EventStream::new(futures::stream::iter(events)).into_response(&req)
+    });
+    app.main_namespace().define_handler("ticker", |req: Request| async move {
+        let (sender, stream) = EventStream::channel(1);
+        tokio::spawn(async move {
+            let mut n = 0;
+            loop {
+                n += 1;
+                if sender.send(SseEvent::new(teon!({ "n": n }))).await.is_err() {
+                    TICKER_DISCONNECTED.store(true, Ordering::SeqCst);
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        stream.heartbeat(Some(Duration::from_millis(10))).into_response(&req)
+    });
+    app.main_namespace().define_handler("replaced", |req: Request| async move {
+        EventStream::new(futures::stream::iter(vec![SseEvent::new(teon!({ "n": 1 }))])).into_response(&req)
+    });
+    // replaces the event stream response of `/replaced` with a JSON one
+    app.main_namespace().define_request_middleware("replace", |_arguments: Arguments| {
+        Ok(|req: Request, next: Next| async move {
+            let replace = req.path() == "/replaced";
+            let response = next.call(req).await?;
+            if replace {
+                return Ok(Response::teon(teon!({ "replaced": true })));
+            }
+            Ok(response)
+        })
+    });
+    Ok(app)
+}
@@ -0,0 +1,91 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use hyper::Method;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::{assert_json, matcher};
+    use crate::server::sse::app::{load_app, TICKER_DISCONNECTED};
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn event_fields() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/events")).await.unwrap();
+        assert_eq!(res.headers().get("content-type").unwrap().unwrap(), "text/event-stream");
+        let events = res.events(2).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.as_deref(), Some("created"));
+        assert_eq!(events[0].id.as_deref(), Some("1"));
+        assert_eq!(events[0].retry, Some(3000));
+        assert_json!(events[0].data_as_json().unwrap(), matcher!({ "n": 1 }));
+        assert_eq!(events[1].event.as_deref(), Some("updated"));
+        assert_eq!(events[1].retry, None);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn stream_end() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/events")).await.unwrap();
+        let events = res.events(10).await.unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].event, None);
+        assert_json!(events[2].data_as_json().unwrap(), matcher!({ "n": 3 }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn heartbeats_between_events() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/ticker")).await.unwrap();
+        let events = res.events(3).await.unwrap();
+        assert_json!(events[2].data_as_json().unwrap(), matcher!({ "n": 3 }));
+        assert!(res.heartbeats().await > 0);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn client_disconnect() {
+        before_all().await;
+        TICKER_DISCONNECTED.store(false, Ordering::SeqCst);
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/ticker")).await.unwrap();
+        assert_eq!(res.events(1).await.unwrap().len(), 1);
+        drop(res);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(TICKER_DISCONNECTED.load(Ordering::SeqCst));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn replaced_response_is_an_error() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/replaced")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 500);
+    }
+}
@@ -0,0 +1,73 @@
+pub mod app;
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Empty};
+    use hyper_util::rt::TokioIo;
+    use serde_json::Value;
+    use serial_test::serial;
+    use tokio::net::TcpStream;
+    use teo::server::server::Server;
+    use crate::server::systemd::app::load_app;
+
+    const CHILD_ENV: &str = "TEO_SYSTEMD_TEST_CHILD";
+
+    /// Runs in the child process started by `serves_inherited_socket`.
+    #[ignore]
+    #[shared_tokio_runtime::runtime_test]
+    async fn serve_inherited_socket_in_child() {
+        if std::env::var(CHILD_ENV).is_err() {
+            return;
+        }
+        // systemd sets this between fork and exec, once the pid of the child is known
+        std::env::set_var("LISTEN_PID", std::process::id().to_string());
+        let server = Server::new(load_app().unwrap());
+        server.setup_app_for_unit_test().await.unwrap();
+        server.serve(false).await.unwrap();
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn serves_inherited_socket() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:4046").unwrap();
+        let fd = listener.as_raw_fd();
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        command
+            .args(["server::systemd::tests::serve_inherited_socket_in_child", "--exact", "--ignored", "--nocapture"])
+            .env(CHILD_ENV, "1")
+            .env("LISTEN_FDS", "1")
+            .stdout(Stdio::piped());
+        unsafe {
+            // pass the socket as the first inherited descriptor like systemd does
+            command.pre_exec(move || {
+                let result = if fd == 3 { libc::fcntl(fd, libc::F_SETFD, 0) } else { libc::dup2(fd, 3) };
+                if result == -1 { Err(std::io::Error::last_os_error()) } else { Ok(()) }
+            });
+        }
+        let mut child = command.spawn().unwrap();
+        // the child accepts from here on
+        drop(listener);
+        let stream = TcpStream::connect("127.0.0.1:4046").await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri("/hello")
+            .header("host", "127.0.0.1:4046")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(30), sender.send_request(req)).await.unwrap().unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["hello"].as_u64(), Some(child.id() as u64));
+        child.kill().unwrap();
+        let output = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("127.0.0.1:4046 (systemd fd 3)"), "{}", stdout);
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::test::schema_path::schema_path_args;
+
+pub static SLOW_FINISHED: AtomicBool = AtomicBool::new(false);
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.timeouts.default = Some(Duration::from_millis(100));
+    config.timeouts.handlers.insert("slowAllowed".to_owned(), Some(Duration::from_secs(2)));
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("slow", |_req: Request| async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        SLOW_FINISHED.store(true, Ordering::SeqCst);
+        Ok(Response::teon(teon!({ "finished": true })))
+    });
+    app.main_namespace().define_handler("slowAllowed", |_req: Request| async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        Ok(Response::teon(teon!({ "finished": true })))
+    });
+    app.main_namespace().define_handler("fast", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "finished": true })))
+    });
+    Ok(app)
+}
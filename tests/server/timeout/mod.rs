@@ -0,0 +1,63 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use hyper::Method;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::{assert_json, matcher};
+    use crate::server::timeout::app::{load_app, SLOW_FINISHED};
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn fast_handler() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/fast")).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({ "finished": true }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn slow_handler_times_out() {
+        before_all().await;
+        SLOW_FINISHED.store(false, Ordering::SeqCst);
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/slow")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 504);
+        assert!(res.body_as_json().unwrap()["error"]["message"].as_str().unwrap().contains("timed out"));
+        // the handler is cancelled rather than left running
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(!SLOW_FINISHED.load(Ordering::SeqCst));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn handler_override() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/slowAllowed")).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::tls::TlsConfig;
+use teo::test::schema_path::schema_path_args;
+
+pub fn cert_dir() -> PathBuf {
+    std::env::temp_dir().join("teo-tls-test")
+}
+
+fn generate_certs() {
+    let dir = cert_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(vec![]).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    let server_key = KeyPair::generate().unwrap();
+    let server_cert = CertificateParams::new(vec!["localhost".to_owned()]).unwrap()
+        .signed_by(&server_key, &ca_cert, &ca_key).unwrap();
+    let client_key = KeyPair::generate().unwrap();
+    let client_cert = CertificateParams::new(vec!["client".to_owned()]).unwrap()
+        .signed_by(&client_key, &ca_cert, &ca_key).unwrap();
+    std::fs::write(dir.join("ca.pem"), ca_cert.pem()).unwrap();
+    std::fs::write(dir.join("server.pem"), server_cert.pem()).unwrap();
+    std::fs::write(dir.join("server.key"), server_key.serialize_pem()).unwrap();
+    std::fs::write(dir.join("client.pem"), client_cert.pem()).unwrap();
+    std::fs::write(dir.join("client.key"), client_key.serialize_pem()).unwrap();
+}
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    generate_certs();
+    let mut config = app.server_config();
+    let mut tls = TlsConfig::new(cert_dir().join("server.pem"), cert_dir().join("server.key"));
+    tls.client_ca = Some(cert_dir().join("ca.pem"));
+    config.tls = Some(tls);
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("hello", |_req: Request| async move {
+        Ok(Response::teon(teon!({
+            "hello": "world"
+        })))
+    });
+    Ok(app)
+}
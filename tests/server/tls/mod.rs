@@ -0,0 +1,138 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use bytes::Bytes;
+    use http_body_util::Empty;
+    use hyper::Version;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use serial_test::serial;
+    use tokio::net::TcpStream;
+    use tokio_rustls::client::TlsStream;
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::rustls::crypto::ring::default_provider;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::TlsConnector;
+    use teo::server::server::Server;
+    use crate::server::tls::app::{cert_dir, load_app};
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        tokio::spawn(async {
+            server().serve(true).await.unwrap();
+        });
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    fn read_certs(name: &str) -> Vec<CertificateDer<'static>> {
+        let file = File::open(cert_dir().join(name)).unwrap();
+        rustls_pemfile::certs(&mut BufReader::new(file)).map(|c| c.unwrap()).collect()
+    }
+
+    fn client_config(with_client_cert: bool, alpn: &[&str]) -> ClientConfig {
+        let mut roots = RootCertStore::empty();
+        for cert in read_certs("ca.pem") {
+            roots.add(cert).unwrap();
+        }
+        let builder = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions().unwrap()
+            .with_root_certificates(roots);
+        let mut config = if with_client_cert {
+            let file = File::open(cert_dir().join("client.key")).unwrap();
+            let key = rustls_pemfile::private_key(&mut BufReader::new(file)).unwrap().unwrap();
+            builder.with_client_auth_cert(read_certs("client.pem"), key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+        config.alpn_protocols = alpn.iter().map(|p| p.as_bytes().to_vec()).collect();
+        config
+    }
+
+    async fn connect(config: ClientConfig) -> std::io::Result<TlsStream<TcpStream>> {
+        let mut stream = None;
+        for _ in 0..50 {
+            if let Ok(s) = TcpStream::connect("127.0.0.1:4022").await {
+                stream = Some(s);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let connector = TlsConnector::from(Arc::new(config));
+        connector.connect(ServerName::try_from("localhost").unwrap(), stream.unwrap()).await
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn https_with_client_certificate() {
+        before_all().await;
+        let stream = connect(client_config(true, &["http/1.1"])).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri("/hello")
+            .header("host", "localhost")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.version(), Version::HTTP_11);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn alpn_negotiates_http2() {
+        before_all().await;
+        let stream = connect(client_config(true, &["h2", "http/1.1"])).await.unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(b"h2".as_slice()));
+        let (mut sender, connection) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream)).await.unwrap();
+        tokio::spawn(connection);
+        let req = hyper::Request::builder()
+            .uri("https://localhost:4022/hello")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.version(), Version::HTTP_2);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn rejects_client_without_certificate() {
+        before_all().await;
+        // With TLS 1.3 the server's rejection may only surface on the first read after the handshake.
+        let result = match connect(client_config(false, &["http/1.1"])).await {
+            Err(_) => Err(()),
+            Ok(stream) => {
+                let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await.unwrap();
+                tokio::spawn(connection);
+                let req = hyper::Request::builder()
+                    .uri("/hello")
+                    .header("host", "localhost")
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                sender.send_request(req).await.map(|_| ()).map_err(|_| ())
+            }
+        };
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::Result;
+use teo::server::trace::{TraceConfig, TraceExporter};
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.trace = Some(TraceConfig {
+        service_name: "trace-test".to_owned(),
+        exporter: TraceExporter::Stdout,
+        response_header: true,
+    });
+    app.replace_server_config(config)?;
+    app.main_namespace().define_handler("hello", |_req: Request| async move {
+        Ok(Response::teon(teon!({ "hello": "world" })))
+    });
+    Ok(app)
+}
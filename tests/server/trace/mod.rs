@@ -0,0 +1,63 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::trace::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    fn traceparent_parts(traceparent: &str) -> Vec<String> {
+        traceparent.split('-').map(|part| part.to_owned()).collect()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn continues_incoming_trace() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/hello")
+            .insert_header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let traceparent = res.headers().get("traceresponse").unwrap().unwrap();
+        let parts = traceparent_parts(&traceparent);
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[1], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_ne!(parts[2], "00f067aa0ba902b7");
+        assert_eq!(parts[3], "01");
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn starts_new_trace() {
+        before_all().await;
+        let res = server().process_test_request(TestRequest::new(Method::GET, "/hello")).await.unwrap();
+        let traceparent = res.headers().get("traceresponse").unwrap().unwrap();
+        let parts = traceparent_parts(&traceparent);
+        assert_eq!(parts.len(), 4);
+        assert_ne!(parts[1], "00000000000000000000000000000000");
+        assert!(res.headers().get("traceparent").unwrap().is_none());
+    }
+}
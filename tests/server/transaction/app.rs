@@ -0,0 +1,46 @@
+use teo_runtime::arguments::Arguments;
+use teo_runtime::middleware::next::{Next, NextImp};
+use teo_runtime::model::Object;
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::result::{Error, Result};
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    let mut config = app.server_config();
+    config.transaction.handlers.insert("Author.createThenFail".to_owned());
+    config.transaction.handlers.insert("Author.inspect".to_owned());
+    app.replace_server_config(config)?;
+    app.main_namespace().define_model_handler_group("Author", |group| {
+        group.define_handler("createThenFail", |request: Request| async move {
+            create_then_fail(request, "opted in").await
+        });
+        group.define_handler("createThenFailWithoutTransaction", |request: Request| async move {
+            create_then_fail(request, "not opted in").await
+        });
+        group.define_handler("inspect", |request: Request| async move {
+            let marker: Option<String> = request.local_values().get("marker")?;
+            Ok(Response::teon(teon!({ "marker": marker })))
+        });
+        Ok(())
+    })?;
+    app.main_namespace().define_request_middleware("mark", |_arguments: Arguments| {
+        Ok(|request: Request, next: Next| async move {
+            request.local_values().insert("marker", "set by middleware".to_owned());
+            Ok(next.call(request).await?)
+        })
+    });
+    Ok(app)
+}
+
+async fn create_then_fail(request: Request, name: &str) -> Result<Response> {
+    let model_ctx = request.transaction_ctx().model_ctx_for_model_at_path(&vec!["Author".to_owned()]).unwrap();
+    let object: Object = model_ctx.create_object(&teon!({ "name": name })).await?;
+    object.save().await?;
+    Err(Error::internal_server_error_message("failed after create"))
+}
@@ -0,0 +1,89 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::server::transaction::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn count(model: &str, args: serde_json::Value) -> u64 {
+        let req = TestRequest::new(Method::POST, &format!("/{}/count", model)).json_body(args).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        res.body_as_json().unwrap()["data"].as_u64().unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn commits_successful_nested_create() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/Author/create").json_body(json!({
+            "create": { "name": "committed", "posts": { "create": [{ "title": "first" }, { "title": "second" }] } }
+        })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(count("Author", json!({ "where": { "name": "committed" } })).await, 1);
+        assert_eq!(count("Post", json!({ "where": { "title": { "in": ["first", "second"] } } })).await, 2);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn rolls_back_nested_create_failing_halfway() {
+        before_all().await;
+        // the second post violates the unique title after the author and the first post are written
+        let req = TestRequest::new(Method::POST, "/Author/create").json_body(json!({
+            "create": { "name": "rolled back", "posts": { "create": [{ "title": "fresh" }, { "title": "fresh" }] } }
+        })).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert!(res.status().as_u16() >= 400);
+        assert_eq!(count("Author", json!({ "where": { "name": "rolled back" } })).await, 0);
+        assert_eq!(count("Post", json!({ "where": { "title": "fresh" } })).await, 0);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn custom_handlers_opt_in() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/Author/createThenFail").json_body(json!({})).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 500);
+        assert_eq!(count("Author", json!({ "where": { "name": "opted in" } })).await, 0);
+        let req = TestRequest::new(Method::POST, "/Author/createThenFailWithoutTransaction").json_body(json!({})).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 500);
+        assert_eq!(count("Author", json!({ "where": { "name": "not opted in" } })).await, 1);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn transactional_handlers_see_middleware_state() {
+        before_all().await;
+        let req = TestRequest::new(Method::POST, "/Author/inspect").json_body(json!({})).await.unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(res.body_as_json().unwrap()["marker"], "set by middleware");
+    }
+}
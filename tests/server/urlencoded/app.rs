@@ -0,0 +1,18 @@
+use teo_runtime::request::Request;
+use teo_runtime::response::Response;
+use teo::app::App;
+use teo::result::Result;
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    app.main_namespace().define_handler("echo", |req: Request| async move {
+        Ok(Response::teon(req.body_value()?.clone()))
+    });
+    app.main_namespace().define_handler("upload", |req: Request| async move {
+        Ok(Response::teon(req.body_value()?.clone()))
+    });
+    Ok(app)
+}
@@ -0,0 +1,107 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use bytes::Bytes;
+    use form_data_builder::FormData;
+    use http_body_util::Full;
+    use hyper::Method;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::{assert_json, matcher};
+    use crate::server::urlencoded::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    async fn urlencoded_request(path: &str, body: &str) -> TestRequest {
+        TestRequest::new(Method::POST, path)
+            .insert_header("content-type", "application/x-www-form-urlencoded").unwrap()
+            .set_body(Full::new(Bytes::from(body.to_owned()))).await.unwrap()
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn plain_fields() {
+        before_all().await;
+        let res = server().process_test_request(urlencoded_request("/upload", "name=John+Doe&email=john%40example.com").await).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "name": "John Doe",
+            "email": "john@example.com",
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn bracket_notation() {
+        before_all().await;
+        let res = server().process_test_request(urlencoded_request("/upload", "tags[]=a&tags[]=b&address[city]=Kathmandu&address[lines][]=1").await).await.unwrap();
+        assert_json!(res.body_as_json().unwrap(), matcher!({
+            "tags": ["a", "b"],
+            "address": {
+                "city": "Kathmandu",
+                "lines": ["1"],
+            },
+        }));
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn same_shape_as_multipart() {
+        before_all().await;
+        let mut form = FormData::new(Vec::new());
+        form.write_field("name", "John").unwrap();
+        let header_value = form.content_type_header();
+        let body = form.finish().unwrap();
+        let req = TestRequest::new(Method::POST, "/upload")
+            .insert_header("content-type", header_value).unwrap()
+            .set_body(Full::new(Bytes::from(body))).await.unwrap();
+        let multipart = server().process_test_request(req).await.unwrap();
+        let urlencoded = server().process_test_request(urlencoded_request("/upload", "name=John").await).await.unwrap();
+        assert_eq!(multipart.body_as_json().unwrap(), urlencoded.body_as_json().unwrap());
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn json_handler_rejects_urlencoded() {
+        before_all().await;
+        let res = server().process_test_request(urlencoded_request("/echo", "a=1").await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 415);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn nesting_depth_over_limit() {
+        before_all().await;
+        let key = format!("a{}", "[b]".repeat(100));
+        let res = server().process_test_request(urlencoded_request("/upload", &format!("{}=1", key)).await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 413);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn conflicting_field_names() {
+        before_all().await;
+        let res = server().process_test_request(urlencoded_request("/upload", "a=1&a[b]=2").await).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+    }
+}
@@ -0,0 +1,42 @@
+use serde_json::json;
+use teo_runtime::arguments::Arguments;
+use teo_runtime::middleware::next::{Next, NextImp};
+use teo_runtime::request::Request;
+use teo_runtime::teon;
+use teo::app::App;
+use teo::prelude::Error;
+use teo::result::Result;
+use teo::server::websocket::{upgrade, WebSocket};
+use teo::test::schema_path::schema_path_args;
+
+pub fn load_app() -> Result<App> {
+    let app = App::new_with_argv(
+        schema_path_args(file!(), "schema.teo")
+    )?;
+    app.main_namespace().define_handler("chat", |req: Request| async move {
+        upgrade(&req, |mut socket: WebSocket| async move {
+            if socket.send_teon(&teon!({ "welcome": true })).await.is_err() {
+                return;
+            }
+            while let Some(Ok(message)) = socket.recv().await {
+                let Ok(value) = message.as_json() else {
+                    break;
+                };
+                if socket.send_json(&json!({ "echo": value })).await.is_err() {
+                    break;
+                }
+            }
+        })
+    });
+    app.main_namespace().define_handler_middleware("auth", |_arguments: Arguments| {
+        Ok(|req: Request, next: Next| async move {
+            if req.headers().get("authorization")?.is_none() {
+                let mut error = Error::new("unauthorized");
+                error.code = 401;
+                return Err(error);
+            }
+            Ok(next.call(req).await?)
+        })
+    });
+    Ok(app)
+}
@@ -0,0 +1,64 @@
+pub mod app;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::OnceCell;
+    use hyper::Method;
+    use serde_json::json;
+    use serial_test::serial;
+    use teo::server::server::Server;
+    use teo::server::test_request::TestRequest;
+    use crate::{assert_json, matcher};
+    use crate::server::websocket::app::load_app;
+
+    static mut SERVER: OnceCell<Server> = OnceCell::new();
+    static mut BEFORE_ALL_EXECUTED: bool = false;
+
+    fn server() -> &'static Server {
+        unsafe { SERVER.get().unwrap() }
+    }
+
+    async fn before_all() {
+        if unsafe { BEFORE_ALL_EXECUTED } {
+            return;
+        }
+        unsafe {
+            SERVER.get_or_init(|| {
+                Server::new(load_app().unwrap())
+            })
+        };
+        server().setup_app_for_unit_test().await.unwrap();
+        unsafe { BEFORE_ALL_EXECUTED = true; }
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn echo_messages() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/chat").insert_header("authorization", "Bearer token").unwrap();
+        let mut socket = server().process_test_websocket(req).await.unwrap();
+        assert_json!(socket.recv_json().await.unwrap(), matcher!({ "welcome": true }));
+        socket.send_json(&json!({ "text": "hello" })).await.unwrap();
+        assert_json!(socket.recv_json().await.unwrap(), matcher!({ "echo": { "text": "hello" } }));
+        socket.send_json(&json!([1, 2])).await.unwrap();
+        assert_json!(socket.recv_json().await.unwrap(), matcher!({ "echo": [1, 2] }));
+        socket.close().await.unwrap();
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn middleware_rejects_handshake() {
+        before_all().await;
+        let result = server().process_test_websocket(TestRequest::new(Method::GET, "/chat")).await;
+        assert_eq!(result.err().unwrap().code, 401);
+    }
+
+    #[serial]
+    #[shared_tokio_runtime::runtime_test]
+    async fn plain_request_is_rejected() {
+        before_all().await;
+        let req = TestRequest::new(Method::GET, "/chat").insert_header("authorization", "Bearer token").unwrap();
+        let res = server().process_test_request(req).await.unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+    }
+}